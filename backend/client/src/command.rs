@@ -0,0 +1,90 @@
+use serde_json::{json, Value};
+use crate::error::CommandError;
+use crate::state::State;
+
+pub const HELP: &[&str] = &[
+    "create <name> [language] [password]   create a room",
+    "join <code> <name> [password]         join a room by its code",
+    "team red|blue                         pick a team",
+    "start <red master> <blue master>      start the game (room admin), bot for the spymaster bot",
+    "hint <word> <guesses>                 give a hint (spymaster)",
+    "guess <word> | guess <row> <col>      turn a card over",
+    "pass                                  end the turn",
+    "reset [language]                      new board (room admin)",
+    "password [password]                   change or remove the password (room admin)",
+    "chat [all|team|master] <message>      send a chat message",
+    "raw <json>                            send a request as is",
+    "help                                  show this help",
+    "quit                                  leave",
+];
+
+pub enum Command {
+    Request(Value),
+    Help,
+    Quit,
+}
+
+pub fn parse(line: &str, state: &State) -> Result<Command, CommandError> {
+    let line = line.trim();
+    let (name, rest) = match line.find(' ') {
+        Some(i) => (&line[..i], line[i + 1..].trim()),
+        None => (line, "")
+    };
+    let args: Vec<&str> = rest.split_whitespace().collect();
+
+    let request = match (name, args.as_slice()) {
+        ("help", _) => return Ok(Command::Help),
+        ("quit", _) | ("exit", _) => return Ok(Command::Quit),
+        ("raw", _) => serde_json::from_str(rest)?,
+        ("create", [name]) => json!({"request": "room", "name": name, "language": "en"}),
+        ("create", [name, language]) => json!({"request": "room", "name": name, "language": language}),
+        ("create", [name, language, password]) => {
+            json!({"request": "room", "name": name, "language": language, "password": password})
+        },
+        ("create", _) => return Err(CommandError::Usage("create <name> [language] [password]")),
+        ("join", [code, name]) => json!({"request": "join", "code": code.to_uppercase(), "name": name}),
+        ("join", [code, name, password]) => {
+            json!({"request": "join", "code": code.to_uppercase(), "name": name, "password": password})
+        },
+        ("join", _) => return Err(CommandError::Usage("join <code> <name> [password]")),
+        ("team", [team]) if *team == "red" || *team == "blue" => json!({"request": "team", "team": team}),
+        ("team", _) => return Err(CommandError::Usage("team red|blue")),
+        ("start", [red, blue]) => {
+            let bots: Vec<&str> = [("red", red), ("blue", blue)].iter()
+                .filter(|(_, master)| **master == "bot")
+                .map(|(team, _)| *team)
+                .collect();
+            json!({"request": "start", "red": red, "blue": blue, "bots": bots})
+        },
+        ("start", _) => return Err(CommandError::Usage("start <red master> <blue master>")),
+        ("hint", [hint, guesses]) => {
+            let guesses: u8 = guesses.parse().map_err(|_| CommandError::Usage("hint <word> <guesses>"))?;
+            json!({"request": "hint", "hint": hint, "guesses": guesses})
+        },
+        ("hint", _) => return Err(CommandError::Usage("hint <word> <guesses>")),
+        ("guess", [word]) => {
+            let (x, y) = state.find(word).ok_or_else(|| CommandError::Word(word.to_string()))?;
+            json!({"request": "guess", "x": x, "y": y})
+        },
+        ("guess", [x, y]) => {
+            let x: usize = x.parse().map_err(|_| CommandError::Usage("guess <row> <col>"))?;
+            let y: usize = y.parse().map_err(|_| CommandError::Usage("guess <row> <col>"))?;
+            json!({"request": "guess", "x": x, "y": y})
+        },
+        ("guess", _) => return Err(CommandError::Usage("guess <word> | guess <row> <col>")),
+        ("pass", []) => json!({"request": "pass"}),
+        ("reset", []) => json!({"request": "reset", "language": state.language}),
+        ("reset", [language]) => json!({"request": "reset", "language": language}),
+        ("password", []) => json!({"request": "password", "password": null}),
+        ("password", [password]) => json!({"request": "password", "password": password}),
+        ("chat", [channel, ..]) if ["all", "team", "master"].contains(channel) => {
+            let message = rest[channel.len()..].trim();
+            json!({"request": "chat", "channel": channel, "message": message})
+        },
+        ("chat", [_, ..]) => json!({"request": "chat", "channel": "all", "message": rest}),
+        ("chat", []) => return Err(CommandError::Usage("chat [all|team|master] <message>")),
+        _ => return Err(CommandError::Unknown(name.to_string()))
+    };
+
+    Ok(Command::Request(request))
+}
@@ -0,0 +1,35 @@
+use thiserror::Error;
+use serde_json::error::Error as SerdeError;
+use tungstenite::Error as WsError;
+use crossterm::ErrorKind as TermError;
+use std::io;
+
+#[derive(Error, Debug)]
+pub enum CommandError {
+    #[error("invalid json: {0}")]
+    Parse(#[from] SerdeError),
+    #[error("usage: {0}")]
+    Usage(&'static str),
+    #[error("unknown command: {0}, type help for the list of commands")]
+    Unknown(String),
+    #[error("no card with the word {0}")]
+    Word(String),
+}
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("connection error: {0}")]
+    Connection(Box<WsError>),
+    #[error("terminal error: {0}")]
+    Terminal(#[from] TermError),
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("connection closed by the server")]
+    Closed,
+}
+
+impl From<WsError> for ClientError {
+    fn from(error: WsError) -> ClientError {
+        ClientError::Connection(Box::new(error))
+    }
+}
@@ -0,0 +1,183 @@
+mod command;
+mod state;
+mod ui;
+mod error;
+
+use clap::{App, Arg};
+use crossterm::{execute, terminal};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use tungstenite::{Message, WebSocket};
+use tungstenite::client::AutoStream;
+use tungstenite::stream::Stream;
+use tungstenite::Error as WsError;
+use command::Command;
+use error::ClientError;
+use state::{Response, State};
+use std::io::{self, Write};
+use std::time::Duration;
+
+const READ_TIMEOUT: Duration = Duration::from_millis(20);
+const INPUT_TIMEOUT: Duration = Duration::from_millis(30);
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), ClientError> {
+    let matches = App::new("codename-client")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Terminal client for the codename server")
+        .arg(Arg::with_name("url")
+            .index(1)
+            .env("CODENAME_URL")
+            .default_value("ws://127.0.0.1:8080/api")
+            .help("server WebSocket url"))
+        .arg(Arg::with_name("language")
+            .long("language")
+            .short("l")
+            .takes_value(true)
+            .default_value("en")
+            .help("board language used by reset"))
+        .arg(Arg::with_name("trace")
+            .long("trace")
+            .help("show the raw requests and responses"))
+        .get_matches();
+
+    let url = matches.value_of("url").unwrap_or_default();
+    let (mut ws, _) = tungstenite::connect(url)?;
+    let socket = match ws.get_mut() {
+        Stream::Plain(s) => s,
+        Stream::Tls(s) => s.get_mut(),
+    };
+    socket.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    let mut client = Client {
+        ws,
+        state: State::new(url, matches.value_of("language").unwrap_or_default()),
+        input: String::new(),
+        trace: matches.is_present("trace"),
+    };
+
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen)?;
+
+    let result = client.run(&mut out);
+
+    execute!(out, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+
+    let _ = client.ws.close(None);
+    let _ = client.ws.write_pending();
+    result
+}
+
+struct Client {
+    ws: WebSocket<AutoStream>,
+    state: State,
+    input: String,
+    trace: bool,
+}
+
+impl Client {
+
+    fn run<W: Write>(&mut self, out: &mut W) -> Result<(), ClientError> {
+        ui::render(out, &self.state, &self.input)?;
+
+        loop {
+            let mut dirty = self.receive()?;
+
+            if event::poll(INPUT_TIMEOUT)? {
+                match event::read()? {
+                    Event::Key(key) => {
+                        if !self.key(key)? {
+                            return Ok(())
+                        }
+                        dirty = true;
+                    },
+                    Event::Resize(_, _) => dirty = true,
+                    _ => {}
+                }
+            }
+
+            if dirty {
+                ui::render(out, &self.state, &self.input)?;
+            }
+        }
+    }
+
+    // returns true when something was received
+    fn receive(&mut self) -> Result<bool, ClientError> {
+        let mut received = false;
+
+        loop {
+            match self.ws.read_message() {
+                Ok(Message::Text(text)) => {
+                    received = true;
+                    if self.trace {
+                        self.state.log(format!("< {}", text));
+                    }
+                    match serde_json::from_str::<Response>(&text) {
+                        Ok(response) => self.state.update(response),
+                        Err(e) => self.state.log(format!("unknown response: {}", e))
+                    }
+                },
+                Ok(Message::Close(_)) => return Err(ClientError::Closed),
+                Ok(_) => {},
+                Err(WsError::Io(e)) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+                    return Ok(received)
+                },
+                Err(WsError::ConnectionClosed) | Err(WsError::AlreadyClosed) => return Err(ClientError::Closed),
+                Err(e) => return Err(e.into())
+            }
+        }
+    }
+
+    // returns false once the user wants to leave
+    fn key(&mut self, key: KeyEvent) -> Result<bool, ClientError> {
+        match key.code {
+            KeyCode::Char('c') | KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(false)
+            },
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            },
+            KeyCode::Esc => self.input.clear(),
+            KeyCode::Enter => {
+                let line: String = self.input.drain(..).collect();
+                if !line.trim().is_empty() {
+                    return self.submit(&line)
+                }
+            },
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn submit(&mut self, line: &str) -> Result<bool, ClientError> {
+        self.state.log(format!("> {}", line));
+
+        match command::parse(line, &self.state) {
+            Ok(Command::Request(request)) => {
+                let text = request.to_string();
+                if self.trace {
+                    self.state.log(format!("> {}", text));
+                }
+                self.ws.write_message(Message::Text(text))?;
+            },
+            Ok(Command::Help) => {
+                for help in command::HELP {
+                    self.state.log(help.to_string());
+                }
+            },
+            Ok(Command::Quit) => return Ok(false),
+            Err(e) => self.state.log(e.to_string())
+        }
+        Ok(true)
+    }
+
+}
@@ -0,0 +1,135 @@
+use serde::Deserialize;
+use codename_engine::board::Tile;
+use codename_engine::team::Team;
+use std::collections::VecDeque;
+
+const LOG: usize = 100;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "response", rename_all = "lowercase")]
+pub enum Response {
+    Error {
+        error: String,
+    },
+    Room {
+        room: Box<Room>,
+    },
+    Tiles {
+        tiles: Vec<Vec<Tile>>,
+    },
+    Chat {
+        messages: Vec<Message>,
+    },
+    Shutdown {
+        eta: Option<u64>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Room {
+    pub code: String,
+    pub game: Game,
+    pub players: Vec<String>,
+    pub state: String,
+    pub private: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Game {
+    pub board: Board,
+    pub red: GameTeam,
+    pub blue: GameTeam,
+    pub turn: Team,
+    pub action: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Board {
+    pub words: Vec<Vec<String>>,
+    pub cards: Vec<Vec<Option<Tile>>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GameTeam {
+    pub master: Option<String>,
+    pub hint: String,
+    pub guesses: u8,
+    pub players: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Message {
+    pub channel: String,
+    pub name: String,
+    pub team: Option<Team>,
+    pub message: String,
+}
+
+// everything the client knows about the room it is in
+pub struct State {
+    pub url: String,
+    pub language: String,
+    pub room: Option<Room>,
+    pub tiles: Option<Vec<Vec<Tile>>>,
+    pub log: VecDeque<String>,
+}
+
+impl State {
+
+    pub fn new(url: &str, language: &str) -> State {
+        State {
+            url: url.to_string(),
+            language: language.to_string(),
+            room: None,
+            tiles: None,
+            log: VecDeque::new(),
+        }
+    }
+
+    pub fn log(&mut self, line: String) {
+        if self.log.len() >= LOG {
+            self.log.pop_front();
+        }
+        self.log.push_back(line);
+    }
+
+    pub fn update(&mut self, response: Response) {
+        match response {
+            Response::Error { error } => self.log(format!("error: {}", error)),
+            Response::Room { room } => {
+                // the key is only valid for the board it was sent with
+                if room.state != "play" && room.state != "end" {
+                    self.tiles = None;
+                }
+                self.room = Some(*room);
+            },
+            Response::Tiles { tiles } => self.tiles = Some(tiles),
+            Response::Chat { messages } => {
+                for m in messages {
+                    let team = match m.team {
+                        Some(Team::Red) => " (red)",
+                        Some(Team::Blue) => " (blue)",
+                        None => ""
+                    };
+                    self.log(format!("[{}] {}{}: {}", m.channel, m.name, team, m.message));
+                }
+            },
+            Response::Shutdown { eta: Some(eta) } => self.log(format!("server restarting in {}s", eta)),
+            Response::Shutdown { eta: None } => self.log("server shutting down".to_string()),
+        }
+    }
+
+    // position of a word on the board, ignoring case
+    pub fn find(&self, word: &str) -> Option<(usize, usize)> {
+        let room = self.room.as_ref()?;
+        for (x, row) in room.game.board.words.iter().enumerate() {
+            for (y, w) in row.iter().enumerate() {
+                if w.eq_ignore_ascii_case(word) {
+                    return Some((x, y))
+                }
+            }
+        }
+        None
+    }
+
+}
@@ -0,0 +1,163 @@
+use crossterm::{cursor, queue, terminal, Result};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::ClearType;
+use codename_engine::board::Tile;
+use codename_engine::team::Team;
+use crate::state::{GameTeam, State};
+use std::io::Write;
+
+const CELL: usize = 14;
+
+pub fn render<W: Write>(out: &mut W, state: &State, input: &str) -> Result<()> {
+    let (width, height) = terminal::size()?;
+    let mut lines: Vec<Line> = Vec::new();
+
+    match &state.room {
+        Some(room) => {
+            let game = &room.game;
+            let lock = if room.private { " (private)" } else { "" };
+            lines.push(Line::text(format!(
+                "room {}{}  state {}  turn {} ({})  players: {}",
+                room.code, lock, room.state, team_name(game.turn), game.action, room.players.join(", ")
+            )));
+            lines.push(team_line(Team::Red, &game.red));
+            lines.push(team_line(Team::Blue, &game.blue));
+            lines.push(Line::text(String::new()));
+
+            let mut header = "   ".to_string();
+            for y in 0..game.board.words.len() {
+                header.push_str(&format!(" {:<width$}", y, width = CELL));
+            }
+            lines.push(Line::text(header));
+
+            for (x, row) in game.board.words.iter().enumerate() {
+                let mut cells = Vec::new();
+                for (y, word) in row.iter().enumerate() {
+                    let revealed = game.board.cards.get(x).and_then(|r| r.get(y)).cloned().flatten();
+                    let key = state.tiles.as_ref().and_then(|t| t.get(x)).and_then(|r| r.get(y)).cloned();
+                    cells.push(Cell {
+                        word: word.clone(),
+                        revealed,
+                        key,
+                    });
+                }
+                lines.push(Line::Row(x, cells));
+            }
+        },
+        None => {
+            lines.push(Line::text(format!("connected to {}", state.url)));
+            lines.push(Line::text("create or join a room, type help for the list of commands".to_string()));
+        }
+    }
+
+    lines.push(Line::text(String::new()));
+
+    // the rest of the screen shows the most recent log lines above the prompt
+    let room = (height as usize).saturating_sub(lines.len() + 1);
+    let skip = state.log.len().saturating_sub(room);
+    for line in state.log.iter().skip(skip) {
+        lines.push(Line::text(line.clone()));
+    }
+
+    queue!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+    for (i, line) in lines.iter().take(height.saturating_sub(1) as usize).enumerate() {
+        queue!(out, cursor::MoveTo(0, i as u16))?;
+        line.render(out, width as usize)?;
+    }
+    queue!(out, cursor::MoveTo(0, height.saturating_sub(1)), Print(truncate(&format!("> {}", input), width as usize)))?;
+    Ok(out.flush()?)
+}
+
+enum Line {
+    Text(String, Option<Color>),
+    Row(usize, Vec<Cell>),
+}
+
+struct Cell {
+    word: String,
+    revealed: Option<Tile>,
+    key: Option<Tile>,
+}
+
+impl Line {
+
+    fn text(text: String) -> Line {
+        Line::Text(text, None)
+    }
+
+    fn render<W: Write>(&self, out: &mut W, width: usize) -> Result<()> {
+        match self {
+            // long lines would wrap over the next ones
+            Line::Text(text, None) => queue!(out, Print(truncate(text, width))),
+            Line::Text(text, Some(color)) => {
+                queue!(out, SetForegroundColor(*color), Print(truncate(text, width)), ResetColor)
+            },
+            Line::Row(x, cells) => {
+                queue!(out, Print(format!("{:>2} ", x)))?;
+                for cell in cells {
+                    let word: String = cell.word.chars().take(CELL - 2).collect();
+                    let text = format!("{:^width$}", word, width = CELL);
+
+                    queue!(out, Print(" "))?;
+                    match (&cell.revealed, &cell.key) {
+                        // turned cards are filled with their colour
+                        (Some(tile), _) => queue!(
+                            out,
+                            SetBackgroundColor(color(tile)),
+                            SetForegroundColor(Color::White),
+                            Print(text),
+                            ResetColor
+                        )?,
+                        // the spymaster sees the colour of every card
+                        (None, Some(tile)) => queue!(
+                            out,
+                            SetForegroundColor(color(tile)),
+                            Print(format!("[{:^width$}]", word, width = CELL - 2)),
+                            ResetColor
+                        )?,
+                        (None, None) => queue!(out, Print(format!("[{:^width$}]", word, width = CELL - 2)))?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+}
+
+fn team_line(team: Team, game: &GameTeam) -> Line {
+    let master = game.master.as_deref().unwrap_or("-");
+    let hint = if game.hint.is_empty() {
+        String::new()
+    } else {
+        format!("  hint \"{}\" ({} left)", game.hint, game.guesses)
+    };
+
+    Line::Text(
+        format!("{:<5} spymaster {}  players: {}{}", team_name(team), master, game.players.join(", "), hint),
+        Some(color(&match team {
+            Team::Red => Tile::Red,
+            Team::Blue => Tile::Blue,
+        }))
+    )
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+fn team_name(team: Team) -> &'static str {
+    match team {
+        Team::Red => "red",
+        Team::Blue => "blue",
+    }
+}
+
+fn color(tile: &Tile) -> Color {
+    match tile {
+        Tile::Red => Color::DarkRed,
+        Tile::Blue => Color::DarkBlue,
+        Tile::Neutral => Color::DarkYellow,
+        Tile::Death => Color::DarkGrey,
+    }
+}
@@ -0,0 +1,90 @@
+use serde::Deserialize;
+use crate::team::Team;
+use crate::error::ValidationError;
+
+type Result = std::result::Result<(), ValidationError>;
+
+pub trait Validate {
+    fn validate(&self) -> Result;
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Start {
+    #[serde(default)]
+    pub blue: String,
+    #[serde(default)]
+    pub red: String,
+    // teams whose spymaster seat is filled by a bot
+    #[serde(default)]
+    pub bots: Vec<Team>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Hint {
+    pub hint: String,
+    pub guesses: u8,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Guess {
+    pub x: usize,
+    pub y: usize
+}
+
+impl Start {
+
+    pub fn master(&self, team: &Team) -> &str {
+        match team {
+            Team::Red => &self.red,
+            Team::Blue => &self.blue,
+        }
+    }
+
+}
+
+impl Validate for Start {
+
+    fn validate(&self) -> Result {
+        if self.blue.is_empty() && !self.bots.contains(&Team::Blue) {
+            return Err(ValidationError::Missing("blue"));
+        }
+        if self.red.is_empty() && !self.bots.contains(&Team::Red) {
+            return Err(ValidationError::Missing("red"));
+        }
+
+        Ok(())
+    }
+
+}
+
+impl Validate for Hint {
+
+    fn validate(&self) -> Result {
+        if self.hint.is_empty() {
+            return Err(ValidationError::Missing("hint"));
+        }
+
+        if !(self.guesses >= 1 && self.guesses <= 9) {
+            return Err(ValidationError::Invalid("guesses must be between 1 and 9"));
+        }
+
+        Ok(())
+    }
+
+}
+
+impl Validate for Guess {
+
+    fn validate(&self) -> Result {
+        if self.x > 4 {
+            return Err(ValidationError::Invalid("x must be between 0 and 4"));
+        }
+
+        if self.y > 4 {
+            return Err(ValidationError::Invalid("y must be between 0 and 4"));
+        }
+
+        Ok(())
+    }
+
+}
@@ -30,7 +30,7 @@ pub struct BoardSet {
     tiles: Vec<TileMap>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Board {
     pub words: WordMap,
     pub cards: CardMap,
@@ -181,7 +181,7 @@ impl Board {
             }
         }
 
-        return (tiles, cards)
+        (tiles, cards)
     }
 
 }
@@ -0,0 +1,63 @@
+use thiserror::Error;
+use std::io;
+
+#[derive(Error, Debug)]
+pub enum ValidationError {
+    #[error("field missing: {0}")]
+    Missing(&'static str),
+    #[error("invalid value: {0}")]
+    Invalid(&'static str),
+}
+
+#[derive(Error, Debug)]
+pub enum GameError {
+    #[error("{0} not found")]
+    NotFound(&'static str),
+    #[error("not your turn to give a {0}")]
+    Turn(&'static str),
+    #[error("player is not a master")]
+    NotMaster,
+    #[error("player is not the admin")]
+    NotAdmin,
+    #[error("team {0} does not have enough players")]
+    MissingPlayers(&'static str),
+    #[error("game has not started")]
+    NotStarted,
+    #[error("game has already started")]
+    AlreadyStarted,
+    #[error("card has already been set")]
+    CardSet,
+}
+
+#[derive(Error, Debug)]
+pub enum BoardError {
+    #[error("language '{0}' not found")]
+    Language(String),
+}
+
+#[derive(Error, Debug)]
+pub enum EmbeddingsError {
+    #[error("unable to read embeddings: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid embeddings on line {0}: {1}")]
+    Invalid(usize, &'static str),
+    #[error("no word vectors found")]
+    Empty,
+}
+
+impl GameError {
+
+    pub fn variant(&self) -> &'static str {
+        match self {
+            GameError::NotFound(_) => "game_not_found",
+            GameError::Turn(_) => "game_turn",
+            GameError::NotMaster => "game_not_master",
+            GameError::NotAdmin => "game_not_admin",
+            GameError::MissingPlayers(_) => "game_missing_players",
+            GameError::NotStarted => "game_not_started",
+            GameError::AlreadyStarted => "game_already_started",
+            GameError::CardSet => "game_card_set",
+        }
+    }
+
+}
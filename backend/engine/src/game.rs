@@ -1,26 +1,26 @@
 use serde::ser::{Serialize, Serializer, SerializeStruct};
-use crate::request;
+use crate::action;
 use crate::board::Board;
 use crate::board::Tile;
 use crate::team::Team;
 use crate::gameteam::GameTeam;
 use crate::error::GameError;
-use mio::Token;
+use crate::player::PlayerId;
 use std::result;
 
 type Result<T> = result::Result<T, GameError>;
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum State {
     Start,
     Play(Team),
     End(Team)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Game {
-    pub admin: Token,
+    pub admin: PlayerId,
     pub board: Board,
     pub state: State,
     pub red: GameTeam,
@@ -34,8 +34,8 @@ impl Serialize for Game {
     {
         let turn = match self.state {
             State::Start => self.board.start_team(),
-            State::Play(team) => team.clone(),
-            State::End(team) => team.clone(),
+            State::Play(team) => team,
+            State::End(team) => team,
         };
 
         let action = match turn {
@@ -56,10 +56,10 @@ impl Serialize for Game {
 
 impl Game {
 
-    pub fn new(board: Board, admin: Token) -> Game {
+    pub fn new(board: Board, admin: PlayerId) -> Game {
         Game {
-            admin: admin,
-            board: board,
+            admin,
+            board,
             state: State::Start,
             red: GameTeam::new(Team::Red),
             blue: GameTeam::new(Team::Blue),
@@ -80,26 +80,42 @@ impl Game {
         }
     }
 
-    pub fn add_player(&mut self, token: Token, team: Team, player: &str) {
-        self.team_mut(&team).add_player(token, player.to_string());
+    pub fn player_team(&self, player: PlayerId) -> Option<Team> {
+        if self.red.players.contains_key(&player) {
+            Some(Team::Red)
+        } else if self.blue.players.contains_key(&player) {
+            Some(Team::Blue)
+        } else {
+            None
+        }
     }
 
-    pub fn remove_player(&mut self, token: Token) -> Option<String> {
-        let result = self.red.remove_player(token).or(self.blue.remove_player(token));
-        if result.is_some() {
-            if !(self.red.playable() && self.blue.playable()) {
-                self.state = State::Start
-            }
+    pub fn masters(&self) -> impl Iterator<Item = PlayerId> {
+        self.red.master.into_iter().chain(self.blue.master)
+    }
+
+    pub fn is_master(&self, player: PlayerId) -> bool {
+        self.masters().any(|t| t == player)
+    }
+
+    pub fn add_player(&mut self, id: PlayerId, team: Team, player: &str) {
+        self.team_mut(&team).add_player(id, player.to_string());
+    }
+
+    pub fn remove_player(&mut self, player: PlayerId) -> Option<String> {
+        let result = self.red.remove_player(player).or(self.blue.remove_player(player));
+        if result.is_some() && !(self.red.playable() && self.blue.playable()) {
+            self.state = State::Start
         }
         result
     }
 
-    pub fn tokens(&self) -> impl Iterator<Item = &Token> {
+    pub fn players(&self) -> impl Iterator<Item = &PlayerId> {
         self.red.players.keys().chain(self.blue.players.keys())
     }
 
-    pub fn start(&mut self, token: Token, start: &request::Start) -> Result<()> {
-        if token != self.admin {
+    pub fn start(&mut self, player: PlayerId, start: &action::Start) -> Result<()> {
+        if player != self.admin {
             return Err(GameError::NotAdmin)
         }
 
@@ -113,8 +129,8 @@ impl Game {
 
         match self.state {
             State::Start => {
-                self.red.set_master(&start)?;
-                self.blue.set_master(&start)?;
+                self.red.set_master(start)?;
+                self.blue.set_master(start)?;
                 self.state = State::Play(self.board.start_team());
             }
             _ => return Err(GameError::AlreadyStarted)
@@ -123,11 +139,11 @@ impl Game {
         Ok(())
     }
 
-    pub fn hint(&mut self, token: Token, hint: &request::Hint) -> Result<()> {
+    pub fn hint(&mut self, player: PlayerId, hint: &action::Hint) -> Result<()> {
         match self.state {
             State::Play(team) => {
                 let gameteam = self.team_mut(&team);
-                gameteam.give_hint(token, &hint)?;
+                gameteam.give_hint(player, hint)?;
                 log::debug!("gave hint: {:?}", gameteam);
             },
             _ => {
@@ -138,10 +154,10 @@ impl Game {
         Ok(())
     }
 
-    pub fn guess(&mut self, token: Token, guess: &request::Guess) -> Result<()> {
+    pub fn guess(&mut self, player: PlayerId, guess: &action::Guess) -> Result<()> {
         match self.state {
             State::Play(team) => {
-                if !self.team(&team).can_guess(token) {
+                if !self.team(&team).can_guess(player) {
                     return Err(GameError::Turn("guess"))
                 }
 
@@ -151,7 +167,7 @@ impl Game {
 
                 match tile {
                     Tile::Blue | Tile::Red | Tile::Neutral => {
-                        let next_team = gameteam.next_team(token, tile)?;
+                        let next_team = gameteam.next_team(player, tile)?;
                         self.state = State::Play(next_team);
                     },
                     Tile::Death => {
@@ -169,10 +185,10 @@ impl Game {
         Ok(())
     }
 
-    pub fn pass(&mut self, token: Token) -> Result<()> {
+    pub fn pass(&mut self, player: PlayerId) -> Result<()> {
         match self.state {
             State::Play(team) => {
-                self.team_mut(&team).pass(token)?;
+                self.team_mut(&team).pass(player)?;
                 self.state = State::Play(team.opposite())
             },
             _ => return Err(GameError::NotStarted)
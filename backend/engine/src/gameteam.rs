@@ -1,33 +1,33 @@
 use serde::Serialize as SerdeSerialize;
 use serde::ser::{Serialize, Serializer, SerializeStruct};
-use crate::request::Hint;
+use crate::action::Hint;
 use crate::team::Team;
 use crate::board::Tile;
 use crate::error::GameError;
-use crate::request;
+use crate::action;
 use std::collections::HashMap;
-use mio::Token;
+use crate::player::PlayerId;
 use std::result;
 
 type Result<T> = result::Result<T, GameError>;
 
 
-#[derive(Debug, SerdeSerialize)]
+#[derive(Debug, Clone, SerdeSerialize)]
 #[serde(rename_all="lowercase")]
 pub enum State {
     Hint,
     Guess
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GameTeam {
     pub team: Team,
     pub guesses: u8,
     pub hint: String,
     pub previous: Option<String>,
-    pub players: HashMap<Token, String>,
+    pub players: HashMap<PlayerId, String>,
     pub state: State,
-    pub master: Option<Token>,
+    pub master: Option<PlayerId>,
 }
 
 impl Serialize for GameTeam {
@@ -36,8 +36,8 @@ impl Serialize for GameTeam {
         S: Serializer,
     {
         let players: Vec<&String> = self.players.values().collect();
-        let master = if let Some(token) = self.master {
-            self.players.get(&token)
+        let master = if let Some(player) = self.master {
+            self.players.get(&player)
         } else {
             None
         };
@@ -56,7 +56,7 @@ impl GameTeam {
 
     pub fn new(team: Team) -> GameTeam {
         GameTeam {
-            team: team,
+            team,
             master: None,
             hint: String::new(),
             guesses: 0,
@@ -66,17 +66,17 @@ impl GameTeam {
         }
     }
 
-    pub fn add_player(&mut self, token: Token, player: String) {
-        self.players.insert(token, player);
+    pub fn add_player(&mut self, id: PlayerId, player: String) {
+        self.players.insert(id, player);
     }
 
-    pub fn remove_player(&mut self, token: Token) -> Option<String> {
+    pub fn remove_player(&mut self, player: PlayerId) -> Option<String> {
         if let Some(master) = self.master {
-            if master == token {
+            if master == player {
                 self.master = None;
             }
         }
-        self.players.remove(&token)
+        self.players.remove(&player)
     }
 
     pub fn nb_players(&self) -> usize {
@@ -87,7 +87,7 @@ impl GameTeam {
         self.players.len() >= 2 && self.master.is_some()
     }
 
-    pub fn set_master(&mut self, start: &request::Start) -> Result<()> {
+    pub fn set_master(&mut self, start: &action::Start) -> Result<()> {
         let name = start.master(&self.team);
 
         self.master = self.players.iter()
@@ -101,8 +101,8 @@ impl GameTeam {
         Ok(())
     }
 
-    pub fn give_hint(&mut self, token: Token, hint: &Hint) -> Result<()> {
-        self.validate_player(token, true)?;
+    pub fn give_hint(&mut self, player: PlayerId, hint: &Hint) -> Result<()> {
+        self.validate_player(player, true)?;
 
         match self.state {
             State::Guess => Err(GameError::Turn("hint")),
@@ -120,8 +120,8 @@ impl GameTeam {
         }
     }
 
-    pub fn next_team(&mut self, token: Token, tile: Tile) -> Result<Team> {
-        self.validate_player(token, false)?;
+    pub fn next_team(&mut self, player: PlayerId, tile: Tile) -> Result<Team> {
+        self.validate_player(player, false)?;
 
         match self.state {
             State::Hint => Err(GameError::Turn("hint")),
@@ -157,33 +157,33 @@ impl GameTeam {
         }
     }
 
-    pub fn pass(&mut self, token: Token) -> Result<()> {
-        self.validate_player(token, false)?;
+    pub fn pass(&mut self, player: PlayerId) -> Result<()> {
+        self.validate_player(player, false)?;
         self.state = State::Hint;
         Ok(())
     }
 
-    fn validate_player(&self, token: Token, master: bool) -> Result<()> {
+    fn validate_player(&self, player: PlayerId, master: bool) -> Result<()> {
         if master {
             match self.master {
-                Some(t) if token == t => Ok(()),
+                Some(t) if player == t => Ok(()),
                 _ => Err(GameError::NotMaster)
             }
         } else {
-            match self.players.get(&token) {
+            match self.players.get(&player) {
                 Some(_) => Ok(()),
                 None => Err(GameError::NotFound("player"))
             }
         }
     }
 
-    pub fn can_guess(&self, token: Token) -> bool {
-        if !self.players.contains_key(&token) {
+    pub fn can_guess(&self, player: PlayerId) -> bool {
+        if !self.players.contains_key(&player) {
             return false
         }
 
         if let Some(master) = self.master {
-            if token == master {
+            if player == master {
                 return false
             }
         }
@@ -0,0 +1,8 @@
+pub mod action;
+pub mod board;
+pub mod error;
+pub mod game;
+pub mod gameteam;
+pub mod player;
+pub mod spymaster;
+pub mod team;
@@ -0,0 +1,273 @@
+use crate::action::Hint;
+use crate::board::{Board, Tile, WordMap};
+use crate::error::EmbeddingsError;
+use crate::team::Team;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+// embedding files list words by frequency, rarer words make poor clues
+const MAX_CANDIDATES: usize = 20000;
+// a clue has to be closer to its words than to any other hidden word by this much
+const MARGIN: f32 = 0.05;
+const ASSASSIN_MARGIN: f32 = 0.15;
+// below this similarity a word is not related to the clue at all
+const MIN_SIMILARITY: f32 = 0.2;
+const MAX_GUESSES: usize = 9;
+const SIZE: usize = 25;
+
+// gives clues from word vectors, in word2vec or GloVe text format
+pub struct Spymaster {
+    words: Vec<String>,
+    index: HashMap<String, usize>,
+    vectors: Vec<f32>,
+    dimension: usize,
+}
+
+// similarities between the clue candidates and the words of a board, row by row
+pub struct Clues {
+    words: WordMap,
+    known: Vec<bool>,
+    candidates: Vec<usize>,
+    similarities: Vec<f32>,
+}
+
+impl Clues {
+
+    pub fn matches(&self, words: &WordMap) -> bool {
+        self.words == *words
+    }
+
+}
+
+impl fmt::Debug for Clues {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Clues {{ candidates: {} }}", self.candidates.len())
+    }
+}
+
+pub fn load_embeddings_file(path: &str) -> Result<Spymaster, EmbeddingsError> {
+    let file = File::open(path)?;
+    Spymaster::from_reader(BufReader::new(file))
+}
+
+impl Spymaster {
+
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Spymaster, EmbeddingsError> {
+        let mut spymaster = Spymaster {
+            words: Vec::new(),
+            index: HashMap::new(),
+            vectors: Vec::new(),
+            dimension: 0,
+        };
+
+        for (n, line) in reader.lines().enumerate() {
+            let line = line?;
+            let mut fields = line.split_whitespace();
+            let word = match fields.next() {
+                Some(word) => word.to_lowercase(),
+                None => continue
+            };
+
+            let mut vector = fields
+                .map(str::parse)
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|_| EmbeddingsError::Invalid(n + 1, "expected numbers after the word"))?;
+
+            // word2vec files start with the number of words and the dimension
+            if n == 0 && vector.len() == 1 {
+                continue
+            }
+
+            if spymaster.dimension == 0 {
+                spymaster.dimension = vector.len();
+            }
+            if vector.is_empty() || vector.len() != spymaster.dimension {
+                return Err(EmbeddingsError::Invalid(n + 1, "vector dimension differs from the first word"))
+            }
+
+            // the most frequent casing of a word is listed first
+            if spymaster.index.contains_key(&word) {
+                continue
+            }
+
+            let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                vector.iter_mut().for_each(|v| *v /= norm);
+            }
+
+            spymaster.index.insert(word.clone(), spymaster.words.len());
+            spymaster.words.push(word);
+            spymaster.vectors.extend(vector);
+        }
+
+        if spymaster.words.is_empty() {
+            return Err(EmbeddingsError::Empty)
+        }
+
+        Ok(spymaster)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    // true if one of the hidden words of the team can be given a clue
+    pub fn knows(&self, board: &Board, team: Team) -> bool {
+        let own_tile = own_tile(team);
+        positions().any(|(x, y)| {
+            !board.cards[x][y] && board.tiles[x][y] == own_tile && self.vector(&board.words[x][y]).is_some()
+        })
+    }
+
+    // the expensive part of a hint, the same for every hint on a board
+    pub fn clues(&self, words: &WordMap) -> Clues {
+        let vectors: Vec<Option<&[f32]>> = positions().map(|(x, y)| self.vector(&words[x][y])).collect();
+        let board_words: Vec<String> = words.iter()
+            .flatten()
+            .map(|word| word.to_lowercase())
+            .collect();
+
+        let mut candidates = Vec::new();
+        let mut similarities = Vec::new();
+
+        for candidate in 0..self.words.len().min(MAX_CANDIDATES) {
+            if !allowed(&self.words[candidate], &board_words) {
+                continue
+            }
+
+            let clue = self.row(candidate);
+            let row: Vec<f32> = vectors.iter()
+                .map(|vector| vector.map(|v| similarity(clue, v)).unwrap_or(0.0))
+                .collect();
+
+            // never close enough to any word to be picked
+            if row.iter().all(|s| *s <= MIN_SIMILARITY) {
+                continue
+            }
+
+            candidates.push(candidate);
+            similarities.extend(row);
+        }
+
+        Clues {
+            words: words.clone(),
+            known: vectors.iter().map(Option::is_some).collect(),
+            candidates,
+            similarities,
+        }
+    }
+
+    // picks the clue relating the most hidden words of the team while staying away
+    // from the others, None if the team has no hidden word with a clue
+    pub fn hint(&self, clues: &Clues, board: &Board, team: Team) -> Option<Hint> {
+        if !clues.matches(&board.words) {
+            return None
+        }
+
+        let own_tile = own_tile(team);
+        let mut own = Vec::new();
+        let mut avoid = Vec::new();
+        for (i, (x, y)) in positions().enumerate() {
+            if board.cards[x][y] || !clues.known[i] {
+                continue
+            }
+
+            match &board.tiles[x][y] {
+                tile if *tile == own_tile => own.push(i),
+                Tile::Death => avoid.push((i, ASSASSIN_MARGIN)),
+                _ => avoid.push((i, MARGIN)),
+            }
+        }
+
+        if own.is_empty() {
+            return None
+        }
+
+        // (score, guesses, candidate)
+        let mut best: Option<(f32, usize, usize)> = None;
+        let mut fallback: Option<(f32, usize)> = None;
+
+        for (c, candidate) in clues.candidates.iter().enumerate() {
+            let row = &clues.similarities[c * SIZE..(c + 1) * SIZE];
+            let danger = avoid.iter()
+                .map(|(i, margin)| row[*i] + margin)
+                .fold(-1.0, f32::max);
+            let limit = danger.max(MIN_SIMILARITY);
+
+            let mut guesses = 0;
+            let mut score = 0.0;
+            let mut closest = f32::MIN;
+            for i in own.iter() {
+                let s = row[*i];
+                closest = closest.max(s);
+                if s > limit {
+                    guesses += 1;
+                    score += s - limit;
+                }
+            }
+
+            if guesses > 0 && best.is_none_or(|(s, _, _)| score > s) {
+                best = Some((score, guesses, *candidate));
+            }
+
+            // when nothing stands out, the clue leaning the most towards one word
+            if fallback.is_none_or(|(s, _)| closest - danger > s) {
+                fallback = Some((closest - danger, *candidate));
+            }
+        }
+
+        let (guesses, candidate) = match (best, fallback) {
+            (Some((_, guesses, candidate)), _) => (guesses.min(MAX_GUESSES), candidate),
+            (None, Some((_, candidate))) => (1, candidate),
+            (None, None) => return None
+        };
+
+        Some(Hint {
+            hint: self.words[candidate].clone(),
+            guesses: guesses as u8,
+        })
+    }
+
+    fn vector(&self, word: &str) -> Option<&[f32]> {
+        self.index.get(&word.to_lowercase()).map(|i| self.row(*i))
+    }
+
+    fn row(&self, index: usize) -> &[f32] {
+        &self.vectors[index * self.dimension..(index + 1) * self.dimension]
+    }
+
+}
+
+impl fmt::Debug for Spymaster {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Spymaster {{ words: {}, dimension: {} }}", self.words.len(), self.dimension)
+    }
+}
+
+fn own_tile(team: Team) -> Tile {
+    match team {
+        Team::Red => Tile::Red,
+        Team::Blue => Tile::Blue,
+    }
+}
+
+fn positions() -> impl Iterator<Item = (usize, usize)> {
+    (0..SIZE).map(|i| (i / 5, i % 5))
+}
+
+// a clue is a single word which is not, and does not contain, a word of the board
+fn allowed(clue: &str, board_words: &[String]) -> bool {
+    clue.chars().count() > 1
+        && clue.chars().all(char::is_alphabetic)
+        && board_words.iter().all(|word| !clue.contains(word.as_str()) && !word.contains(clue))
+}
+
+fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
@@ -0,0 +1,229 @@
+use codename_engine::action::{Guess, Hint, Start};
+use codename_engine::board::{Board, Tile};
+use codename_engine::game::{Game, State};
+use codename_engine::gameteam;
+use codename_engine::player::PlayerId;
+use codename_engine::team::Team;
+use proptest::prelude::*;
+
+// red spymaster, red guesser, blue spymaster, blue guesser and a player in no team
+const PLAYERS: usize = 5;
+
+// actions are mostly played by the team whose turn it is, so that games get to an end
+#[derive(Debug, Clone, Copy)]
+enum Actor {
+    Master,
+    Guesser,
+    Player(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    // a hidden card of the team whose turn it is, as a good guesser would pick
+    Own(usize),
+    Hidden(usize),
+    Card(usize, usize),
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    Hint(Actor, u8),
+    Guess(Actor, Target),
+    Pass(Actor),
+}
+
+fn board() -> impl Strategy<Value = Board> {
+    any::<bool>()
+        .prop_flat_map(|red_starts| {
+            let (first, second) = if red_starts { (Tile::Red, Tile::Blue) } else { (Tile::Blue, Tile::Red) };
+            let mut deck = vec![first; 9];
+            deck.extend(vec![second; 8]);
+            deck.extend(vec![Tile::Neutral; 7]);
+            deck.push(Tile::Death);
+            Just(deck).prop_shuffle()
+        })
+        .prop_map(|deck| Board {
+            words: std::array::from_fn(|x| std::array::from_fn(|y| format!("w{}", x * 5 + y))),
+            cards: [[false; 5]; 5],
+            tiles: std::array::from_fn(|x| std::array::from_fn(|y| deck[x * 5 + y].clone())),
+        })
+}
+
+fn actor() -> impl Strategy<Value = Actor> {
+    prop_oneof![
+        4 => Just(Actor::Master),
+        4 => Just(Actor::Guesser),
+        1 => (0..PLAYERS).prop_map(Actor::Player),
+    ]
+}
+
+fn target() -> impl Strategy<Value = Target> {
+    prop_oneof![
+        4 => (0..25usize).prop_map(Target::Own),
+        2 => (0..25usize).prop_map(Target::Hidden),
+        1 => (0..5usize, 0..5usize).prop_map(|(x, y)| Target::Card(x, y)),
+    ]
+}
+
+fn action() -> impl Strategy<Value = Action> {
+    prop_oneof![
+        2 => (actor(), 1..=9u8).prop_map(|(actor, guesses)| Action::Hint(actor, guesses)),
+        6 => (actor(), target()).prop_map(|(actor, target)| Action::Guess(actor, target)),
+        1 => actor().prop_map(Action::Pass),
+    ]
+}
+
+fn start(board: Board) -> Game {
+    let mut game = Game::new(board, PlayerId(0));
+    game.add_player(PlayerId(0), Team::Red, "r0");
+    game.add_player(PlayerId(1), Team::Red, "r1");
+    game.add_player(PlayerId(2), Team::Blue, "b0");
+    game.add_player(PlayerId(3), Team::Blue, "b1");
+    game.start(PlayerId(0), &Start { red: "r0".to_string(), blue: "b0".to_string(), bots: Vec::new() }).expect("game did not start");
+    game
+}
+
+fn player(game: &Game, actor: Actor) -> PlayerId {
+    let team = match game.state {
+        State::Play(team) | State::End(team) => team,
+        State::Start => game.board.start_team(),
+    };
+
+    match (actor, team) {
+        (Actor::Master, Team::Red) => PlayerId(0),
+        (Actor::Guesser, Team::Red) => PlayerId(1),
+        (Actor::Master, Team::Blue) => PlayerId(2),
+        (Actor::Guesser, Team::Blue) => PlayerId(3),
+        (Actor::Player(player), _) => PlayerId(player),
+    }
+}
+
+fn position(game: &Game, target: Target) -> (usize, usize) {
+    let hidden: Vec<(usize, usize)> = (0..25)
+        .map(|i| (i / 5, i % 5))
+        .filter(|(x, y)| !game.board.cards[*x][*y])
+        .collect();
+
+    match target {
+        Target::Card(x, y) => (x, y),
+        Target::Hidden(n) => hidden[n % hidden.len()],
+        Target::Own(n) => {
+            let tile = match game.state {
+                State::Play(Team::Red) => Tile::Red,
+                _ => Tile::Blue,
+            };
+            let own: Vec<&(usize, usize)> = hidden.iter()
+                .filter(|(x, y)| game.board.tiles[*x][*y] == tile)
+                .collect();
+            match own.len() {
+                0 => hidden[n % hidden.len()],
+                len => *own[n % len],
+            }
+        }
+    }
+}
+
+// returns the guessed card along with whether the action was accepted
+fn apply(game: &mut Game, action: &Action) -> (Option<(usize, usize)>, bool) {
+    match *action {
+        Action::Hint(actor, guesses) => {
+            let hint = Hint { hint: "clue".to_string(), guesses };
+            (None, game.hint(player(game, actor), &hint).is_ok())
+        },
+        Action::Guess(actor, target) => {
+            let (x, y) = position(game, target);
+            (Some((x, y)), game.guess(player(game, actor), &Guess { x, y }).is_ok())
+        },
+        Action::Pass(actor) => (None, game.pass(player(game, actor)).is_ok()),
+    }
+}
+
+fn revealed(game: &Game) -> usize {
+    game.board.cards.iter().flatten().filter(|card| **card).count()
+}
+
+fn cleared(game: &Game, tile: Tile) -> bool {
+    game.board.tiles.iter().flatten()
+        .zip(game.board.cards.iter().flatten())
+        .all(|(t, card)| *t != tile || *card)
+}
+
+fn winner(game: &Game) -> Option<Team> {
+    match game.state {
+        State::End(team) => Some(team),
+        _ => None
+    }
+}
+
+proptest! {
+
+    #[test]
+    fn game_invariants(board in board(), actions in prop::collection::vec(action(), 0..300)) {
+        let mut game = start(board);
+        let mut assassin = None;
+        let mut allowed = 0;
+        let mut guessed = 0;
+
+        for action in actions.iter() {
+            let before = game.clone();
+            let (card, accepted) = apply(&mut game, action);
+
+            if let State::End(_) = before.state {
+                prop_assert!(!accepted, "{:?} accepted after the end", action);
+            }
+
+            // cards are only ever revealed, one per accepted guess
+            let expected = match action {
+                Action::Guess(..) if accepted => revealed(&before) + 1,
+                _ => revealed(&before),
+            };
+            prop_assert_eq!(revealed(&game), expected);
+            for (old, new) in before.board.cards.iter().flatten().zip(game.board.cards.iter().flatten()) {
+                prop_assert!(!old || *new);
+            }
+
+            if let (Some((x, y)), true) = (card, accepted) {
+                if game.board.tiles[x][y] == Tile::Death {
+                    if let State::Play(team) = before.state {
+                        assassin = Some(team);
+                    }
+                }
+            }
+
+            // the game ends exactly when a team is cleared or the assassin is hit
+            let expected = match assassin {
+                Some(team) => Some(team.opposite()),
+                None if cleared(&game, Tile::Blue) => Some(Team::Blue),
+                None if cleared(&game, Tile::Red) => Some(Team::Red),
+                None => None,
+            };
+            prop_assert_eq!(winner(&game), expected);
+
+            match game.state {
+                State::Play(team) => {
+                    // exactly one team's turn, the other waits for its next hint
+                    prop_assert!(matches!(game.team(&team.opposite()).state, gameteam::State::Hint));
+                },
+                State::End(_) => {},
+                State::Start => prop_assert!(false, "game went back to start"),
+            }
+
+            // a hint allows its number of guesses, plus one if the previous hint was not used up
+            match action {
+                Action::Hint(..) if accepted => {
+                    if let State::Play(team) = before.state {
+                        let gameteam = game.team(&team);
+                        allowed = gameteam.guesses as usize + if gameteam.previous.is_some() { 1 } else { 0 };
+                        guessed = 0;
+                    }
+                },
+                Action::Guess(..) if accepted => {
+                    guessed += 1;
+                    prop_assert!(guessed <= allowed, "{} guesses for {} allowed", guessed, allowed);
+                },
+                _ => {}
+            }
+        }
+    }
+
+}
@@ -0,0 +1,102 @@
+use codename_engine::board::{Board, Tile};
+use codename_engine::error::EmbeddingsError;
+use codename_engine::spymaster::Spymaster;
+use codename_engine::team::Team;
+use std::io::Cursor;
+
+// w0 and w1 are red, w2 is blue, w3 is the assassin
+fn board() -> Board {
+    let tile = |i| match i {
+        0 | 1 => Tile::Red,
+        2 => Tile::Blue,
+        3 => Tile::Death,
+        _ => Tile::Neutral,
+    };
+
+    Board {
+        words: std::array::from_fn(|x| std::array::from_fn(|y| format!("w{}", x * 5 + y))),
+        cards: [[false; 5]; 5],
+        tiles: std::array::from_fn(|x| std::array::from_fn(|y| tile(x * 5 + y))),
+    }
+}
+
+// a vector close to the given words of the board
+fn line(word: &str, close: &[usize]) -> String {
+    let vector: Vec<&str> = (0..25).map(|i| if close.contains(&i) { "1" } else { "0" }).collect();
+    format!("{} {}\n", word, vector.join(" "))
+}
+
+fn embeddings(clues: &[(&str, &[usize])]) -> String {
+    let mut text: String = (0..25).map(|i| line(&format!("w{}", i), &[i])).collect();
+    text.extend(clues.iter().map(|(word, close)| line(word, close)));
+    text
+}
+
+fn load(text: &str) -> Result<Spymaster, EmbeddingsError> {
+    Spymaster::from_reader(Cursor::new(text))
+}
+
+fn hint(spymaster: &Spymaster, board: &Board, team: Team) -> Option<(String, u8)> {
+    let clues = spymaster.clues(&board.words);
+    spymaster.hint(&clues, board, team).map(|hint| (hint.hint, hint.guesses))
+}
+
+#[test]
+fn word2vec_header_is_skipped() {
+    let spymaster = load("2 3\napple 1 0 0\npear 0 1 0\n").unwrap();
+    assert_eq!(spymaster.len(), 2);
+}
+
+#[test]
+fn first_casing_is_kept() {
+    // sun points to the red words when its first casing is kept, and to the blue one otherwise
+    let text = embeddings(&[("Sun", &[0]), ("sun", &[2]), ("star", &[1])]);
+    let spymaster = load(&text).unwrap();
+    assert_eq!(spymaster.len(), 27);
+    assert_eq!(hint(&spymaster, &board(), Team::Red), Some(("sun".to_string(), 1)));
+}
+
+#[test]
+fn dimension_mismatch() {
+    match load("apple 1 0\npear 1 0 0\n") {
+        Err(EmbeddingsError::Invalid(2, _)) => {},
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn not_numbers() {
+    match load("apple 1 zero\n") {
+        Err(EmbeddingsError::Invalid(1, _)) => {},
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn empty_file() {
+    for text in ["", "\n\n", "0 300\n"] {
+        match load(text) {
+            Err(EmbeddingsError::Empty) => {},
+            result => panic!("unexpected result {:?} for {:?}", result, text),
+        }
+    }
+}
+
+#[test]
+fn hint_relates_own_words() {
+    let text = embeddings(&[("moon", &[0]), ("sky", &[0, 1]), ("sea", &[0, 1, 3])]);
+    let spymaster = load(&text).unwrap();
+    assert_eq!(hint(&spymaster, &board(), Team::Red), Some(("sky".to_string(), 2)));
+}
+
+#[test]
+fn hint_needs_matching_clues() {
+    let text = embeddings(&[("moon", &[0])]);
+    let spymaster = load(&text).unwrap();
+    let clues = spymaster.clues(&board().words);
+
+    let mut other = board();
+    other.words[0][0] = "w25".to_string();
+    assert!(!clues.matches(&other.words));
+    assert!(spymaster.hint(&clues, &other, Team::Red).is_none());
+}
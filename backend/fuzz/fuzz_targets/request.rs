@@ -0,0 +1,10 @@
+#![no_main]
+use codename::request::Request;
+use libfuzzer_sys::fuzz_target;
+use std::str::FromStr;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = Request::from_str(text);
+    }
+});
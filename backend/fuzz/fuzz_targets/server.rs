@@ -0,0 +1,151 @@
+#![no_main]
+use arbitrary::Arbitrary;
+use codename::memory::{Connection, Memory, Output};
+use codename::ratelimit;
+use codename::response::Response;
+use codename::server::{self, Server};
+use libfuzzer_sys::fuzz_target;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const BOARDS: &str = r#"{
+    "words": {"en": ["w0", "w1", "w2", "w3", "w4", "w5", "w6", "w7", "w8", "w9",
+                     "w10", "w11", "w12", "w13", "w14", "w15", "w16", "w17", "w18", "w19",
+                     "w20", "w21", "w22", "w23", "w24", "w25", "w26", "w27", "w28", "w29"]},
+    "tiles": [[["red", "red", "red", "red", "red"],
+               ["red", "red", "red", "red", "blue"],
+               ["blue", "blue", "blue", "blue", "blue"],
+               ["blue", "blue", "neutral", "neutral", "neutral"],
+               ["neutral", "neutral", "neutral", "neutral", "death"]]]
+}"#;
+
+const MAX_CONNECTIONS: usize = 8;
+const NAMES: [&str; 4] = ["alice", "bob", "carol", "dave"];
+const CHANNELS: usize = 3;
+
+#[derive(Arbitrary, Debug)]
+struct Step {
+    client: u8,
+    op: Op,
+}
+
+// requests are sent as text, so they go through the same parsing and validation as on a socket
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Connect,
+    Close,
+    Text(String),
+    Room(Option<String>),
+    Join(u8, Option<String>),
+    Team(bool),
+    Start(u8, u8),
+    Hint(u8),
+    Guess(u8, u8),
+    Pass,
+    Reset,
+    Password(Option<String>),
+    Chat(u8, String),
+}
+
+fn name(index: u8) -> &'static str {
+    NAMES[index as usize % NAMES.len()]
+}
+
+fn request(step: &Step, codes: &[String]) -> Option<Value> {
+    let request = match &step.op {
+        Op::Connect | Op::Close | Op::Text(_) => return None,
+        Op::Room(language) => json!({
+            "request": "room",
+            "name": name(step.client),
+            "language": language.as_deref().unwrap_or("en"),
+        }),
+        Op::Join(room, password) => json!({
+            "request": "join",
+            "code": codes.get(*room as usize % codes.len().max(1)),
+            "name": name(step.client),
+            "password": password,
+        }),
+        Op::Team(red) => json!({"request": "team", "team": if *red { "red" } else { "blue" }}),
+        Op::Start(red, blue) => json!({"request": "start", "red": name(*red), "blue": name(*blue)}),
+        Op::Hint(guesses) => json!({"request": "hint", "hint": "clue", "guesses": guesses}),
+        Op::Guess(x, y) => json!({"request": "guess", "x": x, "y": y}),
+        Op::Pass => json!({"request": "pass"}),
+        Op::Reset => json!({"request": "reset", "language": "en"}),
+        Op::Password(password) => json!({"request": "password", "password": password}),
+        Op::Chat(channel, message) => {
+            let channel = ["all", "team", "master"][*channel as usize % CHANNELS];
+            json!({"request": "chat", "channel": channel, "message": message})
+        }
+    };
+    Some(request)
+}
+
+// waits for the code of a room just created, room codes are random
+fn created(connection: &Connection) -> Option<String> {
+    while let Ok(output) = connection.recv(Duration::from_millis(200)) {
+        match output {
+            Output::Response(Response::Room { room }) if room.players.len() == 1 => return Some(room.code),
+            Output::Response(Response::Error { .. }) => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+fuzz_target!(|steps: Vec<Step>| {
+    let boardset = serde_json::from_str(BOARDS).expect("invalid boards");
+    let memory = Memory::new();
+    let handle = memory.handle();
+    let config = server::Config {
+        workers: 1,
+        rate_limit: ratelimit::Config {
+            enabled: false,
+            ..ratelimit::Config::default()
+        },
+        ..server::Config::default()
+    };
+
+    let server = thread::spawn(move || {
+        let mut server = Server::new(Arc::new(boardset), memory, config).expect("server did not start");
+        server.run().expect("server failed");
+    });
+
+    let mut connections = vec![handle.connect()];
+    let mut codes = Vec::new();
+
+    for step in steps.iter().take(256) {
+        let index = step.client as usize % connections.len();
+
+        match &step.op {
+            Op::Connect => {
+                if connections.len() < MAX_CONNECTIONS {
+                    connections.push(handle.connect());
+                }
+            },
+            Op::Close => {
+                // a closed socket sends nothing more
+                connections.remove(index).close();
+                if connections.is_empty() {
+                    connections.push(handle.connect());
+                }
+            },
+            Op::Text(text) => connections[index].send_text(text),
+            Op::Room(_) => {
+                if let Some(request) = request(step, &codes) {
+                    connections[index].send_text(&request.to_string());
+                }
+                codes.extend(created(&connections[index]));
+            },
+            _ => {
+                if let Some(request) = request(step, &codes) {
+                    connections[index].send_text(&request.to_string());
+                }
+            }
+        }
+    }
+
+    handle.shutdown();
+    server.join().expect("server panicked");
+});
@@ -0,0 +1,110 @@
+#![no_main]
+use arbitrary::Arbitrary;
+use codename::response;
+use codename::stream::{self, Flush};
+use codename::transport::{self, EventKind};
+use libfuzzer_sys::fuzz_target;
+use mio::Token;
+use tungstenite::{Message, WebSocket};
+use tungstenite::protocol::Role;
+use tungstenite::protocol::frame::CloseFrame;
+use tungstenite::protocol::frame::coding::CloseCode;
+use std::collections::VecDeque;
+use std::io::{self, Cursor, Read, Write};
+
+const TOKEN: Token = Token(1);
+
+#[derive(Arbitrary, Debug)]
+struct Input<'a> {
+    max_queue: u8,
+    // bytes the socket takes before it blocks, so that responses back up
+    writable: u16,
+    pings: bool,
+    frames: &'a [u8],
+}
+
+// a nonblocking socket which has received the fuzz input after the handshake
+struct Socket<'a> {
+    input: Cursor<&'a [u8]>,
+    writable: usize,
+}
+
+impl Read for Socket<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.input.read(buf)? {
+            0 if !buf.is_empty() => Err(io::ErrorKind::WouldBlock.into()),
+            n => Ok(n)
+        }
+    }
+}
+
+impl Write for Socket<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.writable == 0 && !buf.is_empty() {
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+
+        let n = buf.len().min(self.writable);
+        self.writable -= n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// plays the websocket side of Stream: reads, answers every request, pings, and closes
+fuzz_target!(|input: Input| {
+    // small messages so that the size limit is within reach
+    let config = stream::Config {
+        max_message_size: 1 << 10,
+        max_queue: input.max_queue as usize,
+        ..stream::Config::default()
+    };
+    let socket = Socket {
+        input: Cursor::new(input.frames),
+        writable: input.writable as usize,
+    };
+    let mut ws = WebSocket::from_raw_socket(socket, Role::Server, Some(config.websocket()));
+    let mut queue = VecDeque::new();
+
+    let answer = transport::encode(&response::error("fuzz")).expect("error response does not encode");
+
+    loop {
+        let mut events = Vec::new();
+        let read = stream::read_messages(&mut ws, TOKEN, false, &mut events);
+
+        let mut closed = false;
+        for event in events {
+            match event.kind {
+                EventKind::Request(_) | EventKind::Error(_) => {
+                    closed |= !stream::enqueue(&mut queue, answer.clone(), config.max_queue);
+                },
+                EventKind::Close => closed = true,
+                _ => {}
+            }
+        }
+
+        if input.pings {
+            closed |= !stream::enqueue(&mut queue, Message::Ping(Vec::new()), config.max_queue);
+        }
+
+        if let Flush::Closed = stream::write_messages(&mut ws, TOKEN, Some(&mut queue)) {
+            closed = true;
+        }
+
+        if closed || !read {
+            break
+        }
+    }
+
+    // a server side close, as on shutdown
+    queue.clear();
+    stream::enqueue(&mut queue, Message::Close(Some(CloseFrame {
+        code: CloseCode::Away,
+        reason: "server shutting down".into(),
+    })), config.max_queue.max(1));
+    stream::write_messages(&mut ws, TOKEN, Some(&mut queue));
+    stream::read_messages(&mut ws, TOKEN, true, &mut Vec::new());
+});
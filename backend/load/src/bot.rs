@@ -0,0 +1,109 @@
+use serde::Deserialize;
+use serde_json::Value;
+use codename_engine::board::Tile;
+use codename_engine::team::Team;
+use tungstenite::{Message, WebSocket};
+use tungstenite::client::AutoStream;
+use tungstenite::stream::Stream;
+use tungstenite::Error as WsError;
+use crate::error::SimError;
+use std::io;
+use std::time::Duration;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "response", rename_all = "lowercase")]
+pub enum Response {
+    Error {
+        error: String,
+    },
+    Room {
+        room: Box<Room>,
+    },
+    // the bots play without looking at the key or the chat
+    Tiles {},
+    Chat {},
+    Shutdown {},
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Room {
+    pub code: String,
+    pub game: Game,
+    pub state: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Game {
+    pub board: Board,
+    pub turn: Team,
+    pub action: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Board {
+    pub cards: Vec<Vec<Option<Tile>>>,
+}
+
+pub struct Bot {
+    pub name: String,
+    pub team: Team,
+    pub master: bool,
+    ws: WebSocket<AutoStream>,
+}
+
+impl Bot {
+
+    pub fn connect(url: &str, name: String, timeout: Duration) -> Result<Bot, SimError> {
+        let (mut ws, _) = tungstenite::connect(url)?;
+        let socket = match ws.get_mut() {
+            Stream::Plain(s) => s,
+            Stream::Tls(s) => s.get_mut(),
+        };
+        socket.set_read_timeout(Some(timeout)).map_err(WsError::Io)?;
+
+        Ok(Bot {
+            name,
+            team: Team::Red,
+            master: false,
+            ws,
+        })
+    }
+
+    pub fn send(&mut self, request: &Value) -> Result<(), SimError> {
+        self.ws.write_message(Message::Text(request.to_string()))?;
+        Ok(())
+    }
+
+    pub fn recv(&mut self) -> Result<Response, SimError> {
+        loop {
+            match self.ws.read_message() {
+                Ok(Message::Text(text)) => return Ok(serde_json::from_str(&text)?),
+                Ok(Message::Close(_)) => return Err(SimError::Closed),
+                Ok(_) => {},
+                Err(WsError::Io(e)) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+                    return Err(SimError::Timeout)
+                },
+                Err(WsError::ConnectionClosed) | Err(WsError::AlreadyClosed) => return Err(SimError::Closed),
+                Err(e) => return Err(e.into())
+            }
+        }
+    }
+
+    // waits for the next room update, an error response fails the wait
+    pub fn room(&mut self) -> Result<Room, SimError> {
+        loop {
+            match self.recv()? {
+                Response::Room { room } => return Ok(*room),
+                Response::Error { error } => return Err(SimError::Server(error)),
+                Response::Shutdown { .. } => return Err(SimError::Closed),
+                Response::Tiles { .. } | Response::Chat { .. } => {}
+            }
+        }
+    }
+
+    pub fn close(&mut self) {
+        let _ = self.ws.close(None);
+        let _ = self.ws.write_pending();
+    }
+
+}
@@ -0,0 +1,25 @@
+use thiserror::Error;
+use serde_json::error::Error as SerdeError;
+use tungstenite::Error as WsError;
+
+#[derive(Error, Debug)]
+pub enum SimError {
+    #[error("connection error: {0}")]
+    Connection(Box<WsError>),
+    #[error("invalid response: {0}")]
+    Parse(#[from] SerdeError),
+    #[error("timed out waiting for a response")]
+    Timeout,
+    #[error("connection closed by the server")]
+    Closed,
+    #[error("server error: {0}")]
+    Server(String),
+    #[error("protocol error: {0}")]
+    Protocol(&'static str),
+}
+
+impl From<WsError> for SimError {
+    fn from(error: WsError) -> SimError {
+        SimError::Connection(Box::new(error))
+    }
+}
@@ -0,0 +1,84 @@
+mod bot;
+mod sim;
+mod stats;
+mod error;
+
+use clap::{App, Arg, ArgMatches};
+use stats::Stats;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn main() {
+    let matches = App::new("codename-load")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Plays simulated games against a codename server and reports latencies")
+        .arg(Arg::with_name("url")
+            .index(1)
+            .default_value("ws://127.0.0.1:8080/api")
+            .help("server WebSocket url"))
+        .arg(option("rooms", "10", "number of rooms played concurrently"))
+        .arg(option("players", "4", "players per room, at least 4"))
+        .arg(option("games", "1", "games played in each room"))
+        .arg(option("language", "en", "board language"))
+        .arg(option("timeout", "10", "seconds to wait for a response"))
+        .arg(option("delay", "0", "milliseconds between moves"))
+        .arg(option("max-moves", "500", "moves after which a game is abandoned"))
+        .arg(option("seed", "0", "random seed, room n plays with seed + n"))
+        .get_matches();
+
+    let config = sim::Config {
+        url: matches.value_of("url").unwrap_or_default().to_string(),
+        players: parse::<usize>(&matches, "players").max(4),
+        games: parse(&matches, "games"),
+        language: matches.value_of("language").unwrap_or_default().to_string(),
+        timeout: Duration::from_secs(parse(&matches, "timeout")),
+        delay: Duration::from_millis(parse(&matches, "delay")),
+        max_moves: parse(&matches, "max-moves"),
+    };
+    let rooms: usize = parse(&matches, "rooms");
+    let seed: u64 = parse(&matches, "seed");
+
+    println!("playing {} games in {} rooms of {} players against {}", config.games, rooms, config.players, config.url);
+    let start = Instant::now();
+
+    let handles: Vec<_> = (0..rooms)
+        .map(|i| {
+            let config = config.clone();
+            thread::spawn(move || sim::run(i, &config, seed.wrapping_add(i as u64)))
+        })
+        .collect();
+
+    let mut stats = Stats::default();
+    for handle in handles {
+        match handle.join() {
+            Ok(room) => stats.merge(room),
+            Err(_) => stats.error("simulation panicked".to_string())
+        }
+    }
+
+    stats.report(start.elapsed());
+
+    if !stats.errors.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+fn option<'a>(name: &'a str, default: &'a str, help: &'a str) -> Arg<'a, 'a> {
+    Arg::with_name(name)
+        .long(name)
+        .takes_value(true)
+        .default_value(default)
+        .help(help)
+}
+
+fn parse<T: FromStr>(matches: &ArgMatches, name: &str) -> T {
+    let value = matches.value_of(name).unwrap_or_default();
+    match value.parse() {
+        Ok(value) => value,
+        Err(_) => {
+            eprintln!("error: invalid value for {}: {}", name, value);
+            std::process::exit(1);
+        }
+    }
+}
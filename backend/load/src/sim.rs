@@ -0,0 +1,176 @@
+use serde_json::{json, Value};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use codename_engine::team::Team;
+use crate::bot::{Bot, Room};
+use crate::error::SimError;
+use crate::stats::Stats;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub url: String,
+    pub players: usize,
+    pub games: usize,
+    pub language: String,
+    pub timeout: Duration,
+    pub delay: Duration,
+    pub max_moves: usize,
+}
+
+struct Simulation<'a> {
+    config: &'a Config,
+    bots: Vec<Bot>,
+    joined: usize,
+    rng: StdRng,
+}
+
+// plays the configured number of games in one room
+pub fn run(index: usize, config: &Config, seed: u64) -> Stats {
+    let mut stats = Stats::default();
+
+    let mut bots = Vec::new();
+    for i in 0..config.players {
+        match Bot::connect(&config.url, format!("bot-{}-{}", index, i), config.timeout) {
+            Ok(bot) => bots.push(bot),
+            Err(e) => {
+                stats.error(e.to_string());
+                return stats
+            }
+        }
+    }
+
+    let mut simulation = Simulation {
+        config,
+        bots,
+        joined: 0,
+        rng: StdRng::seed_from_u64(seed),
+    };
+
+    if let Err(e) = simulation.play(&mut stats) {
+        stats.error(e.to_string());
+    }
+
+    for bot in simulation.bots.iter_mut() {
+        bot.close();
+    }
+    stats
+}
+
+impl<'a> Simulation<'a> {
+
+    fn play(&mut self, stats: &mut Stats) -> Result<(), SimError> {
+        let request = json!({"request": "room", "name": self.bots[0].name, "language": self.config.language});
+        let room = self.exchange(stats, 0, "room", request)?;
+        self.joined = 1;
+
+        for i in 1..self.bots.len() {
+            let request = json!({"request": "join", "code": room.code, "name": self.bots[i].name});
+            self.joined = i + 1;
+            self.exchange(stats, i, "join", request)?;
+        }
+
+        for game in 0..self.config.games {
+            if game > 0 {
+                let request = json!({"request": "reset", "language": self.config.language});
+                self.exchange(stats, 0, "reset", request)?;
+            }
+
+            self.game(stats)?;
+            stats.games += 1;
+        }
+
+        Ok(())
+    }
+
+    fn game(&mut self, stats: &mut Stats) -> Result<(), SimError> {
+        // even bots play red and odd bots blue, the first of each team is its spymaster
+        for i in 0..self.bots.len() {
+            let team = if i % 2 == 0 { Team::Red } else { Team::Blue };
+            self.bots[i].team = team;
+            self.bots[i].master = i < 2;
+
+            let request = json!({"request": "team", "team": team});
+            self.exchange(stats, i, "team", request)?;
+        }
+
+        let request = json!({"request": "start", "red": self.bots[0].name, "blue": self.bots[1].name});
+        let mut room = self.exchange(stats, 0, "start", request)?;
+
+        for _ in 0..self.config.max_moves {
+            if room.state == "end" {
+                return Ok(())
+            }
+
+            if !self.config.delay.is_zero() {
+                thread::sleep(self.config.delay);
+            }
+
+            room = self.turn(stats, &room)?;
+            stats.moves += 1;
+        }
+
+        Err(SimError::Protocol("game did not end"))
+    }
+
+    // plays a random legal move for the team whose turn it is
+    fn turn(&mut self, stats: &mut Stats, room: &Room) -> Result<Room, SimError> {
+        let team = room.game.turn;
+
+        if room.game.action == "hint" {
+            let master = self.bots.iter()
+                .position(|b| b.team == team && b.master)
+                .ok_or(SimError::Protocol("no spymaster"))?;
+            let guesses: u8 = self.rng.gen_range(1, 4);
+            let request = json!({"request": "hint", "hint": format!("clue{}", stats.moves), "guesses": guesses});
+            return self.exchange(stats, master, "hint", request)
+        }
+
+        let guessers: Vec<usize> = self.bots.iter()
+            .enumerate()
+            .filter(|(_, b)| b.team == team && !b.master)
+            .map(|(i, _)| i)
+            .collect();
+        let guesser = *guessers.choose(&mut self.rng).ok_or(SimError::Protocol("no guesser"))?;
+
+        let mut hidden = Vec::new();
+        for (x, row) in room.game.board.cards.iter().enumerate() {
+            for (y, card) in row.iter().enumerate() {
+                if card.is_none() {
+                    hidden.push((x, y));
+                }
+            }
+        }
+
+        match hidden.choose(&mut self.rng) {
+            Some((x, y)) if !self.rng.gen_bool(0.15) => {
+                let request = json!({"request": "guess", "x": x, "y": y});
+                self.exchange(stats, guesser, "guess", request)
+            },
+            _ => self.exchange(stats, guesser, "pass", json!({"request": "pass"}))
+        }
+    }
+
+    // sends a request and waits for the update to reach every player in the room
+    fn exchange(&mut self, stats: &mut Stats, actor: usize, kind: &'static str, request: Value) -> Result<Room, SimError> {
+        let start = Instant::now();
+        self.bots[actor].send(&request)?;
+
+        let room = match self.bots[actor].room() {
+            Ok(room) => room,
+            Err(SimError::Server(error)) => return Err(SimError::Server(format!("{}: {}", kind, error))),
+            Err(e) => return Err(e)
+        };
+        stats.request(kind, start.elapsed());
+
+        for i in 0..self.joined {
+            if i != actor {
+                self.bots[i].room()?;
+            }
+        }
+
+        Ok(room)
+    }
+
+}
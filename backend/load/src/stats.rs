@@ -0,0 +1,91 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct Stats {
+    pub games: usize,
+    pub moves: usize,
+    pub latencies: HashMap<&'static str, Vec<Duration>>,
+    pub errors: BTreeMap<String, usize>,
+}
+
+impl Stats {
+
+    pub fn request(&mut self, kind: &'static str, latency: Duration) {
+        self.latencies.entry(kind).or_default().push(latency);
+    }
+
+    pub fn error(&mut self, error: String) {
+        *self.errors.entry(error).or_insert(0) += 1;
+    }
+
+    pub fn merge(&mut self, other: Stats) {
+        self.games += other.games;
+        self.moves += other.moves;
+        for (kind, latencies) in other.latencies {
+            self.latencies.entry(kind).or_default().extend(latencies);
+        }
+        for (error, count) in other.errors {
+            *self.errors.entry(error).or_insert(0) += count;
+        }
+    }
+
+    pub fn report(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        let requests: usize = self.latencies.values().map(Vec::len).sum();
+        let errors: usize = self.errors.values().sum();
+
+        println!("elapsed     {:.2}s", secs);
+        println!("games       {} ({:.2}/s)", self.games, self.games as f64 / secs);
+        println!("moves       {}", self.moves);
+        println!("requests    {} ({:.1}/s)", requests, requests as f64 / secs);
+        println!("errors      {}", errors);
+        println!();
+
+        println!("{:<10} {:>8} {:>10} {:>10} {:>10} {:>10}", "request", "count", "p50", "p90", "p99", "max");
+        let mut all = Vec::new();
+        let mut kinds: Vec<&&'static str> = self.latencies.keys().collect();
+        kinds.sort();
+        for kind in kinds {
+            let latencies = &self.latencies[*kind];
+            all.extend_from_slice(latencies);
+            row(kind, latencies.clone());
+        }
+        row("all", all);
+
+        if !self.errors.is_empty() {
+            println!();
+            for (error, count) in &self.errors {
+                println!("{:>8}  {}", count, error);
+            }
+        }
+    }
+
+}
+
+fn row(kind: &str, mut latencies: Vec<Duration>) {
+    if latencies.is_empty() {
+        return
+    }
+    latencies.sort();
+
+    println!(
+        "{:<10} {:>8} {:>10} {:>10} {:>10} {:>10}",
+        kind,
+        latencies.len(),
+        millis(percentile(&latencies, 50.0)),
+        millis(percentile(&latencies, 90.0)),
+        millis(percentile(&latencies, 99.0)),
+        millis(latencies[latencies.len() - 1]),
+    );
+}
+
+// nearest rank percentile of sorted latencies
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,87 @@
+use mio::net::TcpStream;
+use std::io::{self, Read, Write};
+
+pub const MAX_REQUEST: usize = 8 << 10;
+
+pub struct Connection {
+    pub socket: TcpStream,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    written: usize,
+}
+
+impl Connection {
+
+    pub fn new(socket: TcpStream) -> Connection {
+        Connection {
+            socket,
+            input: Vec::new(),
+            output: Vec::new(),
+            written: 0,
+        }
+    }
+
+    // returns the requested path once the request headers have been received
+    pub fn read(&mut self) -> io::Result<Option<String>> {
+        let mut buf = [0u8; 1024];
+
+        loop {
+            match self.socket.read(&mut buf) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.input.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e)
+            }
+
+            if self.input.len() > MAX_REQUEST {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "request too large"))
+            }
+        }
+
+        parse(&self.input)
+    }
+
+    pub fn respond(&mut self, status: u16, reason: &str, content_type: &str, body: &str) {
+        self.output = response(status, reason, content_type, body);
+        self.written = 0;
+    }
+
+    pub fn responded(&self) -> bool {
+        !self.output.is_empty()
+    }
+
+    // returns true once the whole response has been written
+    pub fn write(&mut self) -> io::Result<bool> {
+        while self.written < self.output.len() {
+            match self.socket.write(&self.output[self.written..]) {
+                Ok(n) => self.written += n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(e) => return Err(e)
+            }
+        }
+        Ok(self.responded())
+    }
+
+}
+
+// returns the requested path once the request headers are complete
+pub fn parse(input: &[u8]) -> io::Result<Option<String>> {
+    if !input.windows(4).any(|w| w == b"\r\n\r\n") {
+        return Ok(None)
+    }
+
+    let request = String::from_utf8_lossy(input);
+    let mut line = request.lines().next().unwrap_or("").split_whitespace();
+
+    match (line.next(), line.next()) {
+        (Some("GET"), Some(path)) => Ok(Some(path.to_string())),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "invalid request line"))
+    }
+}
+
+pub fn response(status: u16, reason: &str, content_type: &str, body: &str) -> Vec<u8> {
+    format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason, content_type, body.len(), body
+    ).into_bytes()
+}
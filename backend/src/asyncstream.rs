@@ -0,0 +1,609 @@
+use crate::stream::Config;
+use crate::transport::{self, Transport, Event, EventKind, Waker};
+use crate::upgrade::Check;
+use crate::socket;
+use crate::admin;
+use crate::line;
+use mio::Token;
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::{self, Runtime};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time;
+use tokio_native_tls::TlsAcceptor;
+use tungstenite::Message;
+use tungstenite::Error as WsError;
+use tungstenite::protocol::frame::CloseFrame;
+use tungstenite::protocol::frame::coding::CloseCode;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+const SIGNAL: Token = Token(usize::MAX);
+const TICK: Duration = Duration::from_secs(1);
+
+// everything the connection tasks hand over to the thread running the server
+enum Input {
+    Open(Token, Peer),
+    Event(Event),
+    Wake,
+}
+
+#[derive(Clone, Copy)]
+enum Listener {
+    Ws,
+    Admin,
+    Tcp,
+}
+
+enum Peer {
+    // websocket and line connections both take messages
+    Ws(mpsc::Sender<Message>, IpAddr),
+    Http(oneshot::Sender<Vec<u8>>),
+}
+
+struct Connection {
+    outbound: mpsc::Sender<Message>,
+    ip: IpAddr,
+    closing: bool,
+}
+
+struct Shared {
+    config: Config,
+    acceptor: Option<TlsAcceptor>,
+    input: Sender<Input>,
+    next: AtomicUsize,
+    open: AtomicUsize,
+    per_ip: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl Shared {
+
+    fn token(&self) -> Token {
+        Token(self.next.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn send(&self, input: Input) {
+        let _ = self.input.send(input);
+    }
+
+    fn event(&self, token: Token, kind: EventKind) {
+        self.send(Input::Event(Event{
+            token,
+            kind
+        }))
+    }
+
+    fn admit(&self, ip: IpAddr) -> Option<&'static str> {
+        let open = self.open.fetch_add(1, Ordering::Relaxed);
+        let mut per_ip = self.per_ip.lock().unwrap();
+        let count = per_ip.entry(ip).or_insert(0);
+        *count += 1;
+
+        if let Some(max) = self.config.max_connections {
+            if open >= max {
+                return Some("too many connections")
+            }
+        }
+
+        if let Some(max) = self.config.max_connections_per_ip {
+            if *count > max {
+                return Some("too many connections from this address")
+            }
+        }
+
+        None
+    }
+
+    fn release(&self, ip: IpAddr) {
+        self.open.fetch_sub(1, Ordering::Relaxed);
+        let mut per_ip = self.per_ip.lock().unwrap();
+        if let Some(count) = per_ip.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                per_ip.remove(&ip);
+            }
+        }
+    }
+
+}
+
+struct Wake(Sender<Input>);
+
+impl Waker for Wake {
+    fn wake(&self) -> io::Result<()> {
+        self.0.send(Input::Wake)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "stream is gone"))
+    }
+}
+
+pub struct AsyncStream {
+    runtime: Option<Runtime>,
+    input: Receiver<Input>,
+    sender: Sender<Input>,
+    stop: watch::Sender<bool>,
+    ws: HashMap<Token, Connection>,
+    http: HashMap<Token, oneshot::Sender<Vec<u8>>>,
+}
+
+impl AsyncStream {
+
+    pub fn new(addr: SocketAddr, config: Config) -> io::Result<AsyncStream> {
+        let runtime = runtime::Builder::new_multi_thread()
+            .thread_name("transport")
+            .enable_all()
+            .build()?;
+
+        let acceptor = match &config.tls {
+            Some(tls) => Some(TlsAcceptor::from(socket::acceptor(tls)?)),
+            None => None
+        };
+
+        let (sender, input) = channel();
+        let (stop, stopped) = watch::channel(false);
+
+        let shared = Arc::new(Shared {
+            config: config.clone(),
+            acceptor,
+            input: sender.clone(),
+            next: AtomicUsize::new(1),
+            open: AtomicUsize::new(0),
+            per_ip: Mutex::new(HashMap::new()),
+        });
+
+        let listener = runtime.block_on(TcpListener::bind(addr))?;
+        runtime.spawn(accept(listener, shared.clone(), stopped.clone(), Listener::Ws));
+
+        if let Some(admin) = config.admin {
+            let listener = runtime.block_on(TcpListener::bind(admin))?;
+            runtime.spawn(accept(listener, shared.clone(), stopped.clone(), Listener::Admin));
+        }
+
+        if let Some(tcp) = config.tcp {
+            let listener = runtime.block_on(TcpListener::bind(tcp))?;
+            runtime.spawn(accept(listener, shared.clone(), stopped, Listener::Tcp));
+        }
+
+        runtime.spawn(signals(shared));
+
+        Ok(AsyncStream {
+            runtime: Some(runtime),
+            input,
+            sender,
+            stop,
+            ws: HashMap::new(),
+            http: HashMap::new(),
+        })
+    }
+
+    fn handle(&mut self, input: Input, events: &mut Vec<Event>) {
+        match input {
+            Input::Open(token, Peer::Ws(outbound, ip)) => {
+                self.ws.insert(token, Connection {
+                    outbound,
+                    ip,
+                    closing: false,
+                });
+            },
+            Input::Open(token, Peer::Http(response)) => {
+                self.http.insert(token, response);
+            },
+            Input::Event(event) => events.push(event),
+            Input::Wake => {}
+        }
+    }
+
+}
+
+impl Transport for AsyncStream {
+
+    fn waker(&self) -> io::Result<Arc<dyn Waker>> {
+        Ok(Arc::new(Wake(self.sender.clone())))
+    }
+
+    fn poll(&mut self) -> io::Result<Vec<Event>> {
+        let mut events = Vec::new();
+
+        log::debug!("polling");
+        match self.input.recv_timeout(TICK) {
+            Ok(input) => self.handle(input, &mut events),
+            Err(RecvTimeoutError::Timeout) => {},
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "transport stopped"))
+            }
+        }
+
+        while let Ok(input) = self.input.try_recv() {
+            self.handle(input, &mut events);
+        }
+
+        Ok(events)
+    }
+
+    fn shutdown(&mut self, timeout: Duration) -> io::Result<()> {
+        let _ = self.stop.send(true);
+        self.http.clear();
+
+        let tokens: Vec<Token> = self.ws.keys().copied().collect();
+        for token in tokens {
+            self.close(token, CloseCode::Away, "server shutting down");
+        }
+
+        let deadline = Instant::now() + timeout;
+        while !self.ws.is_empty() && Instant::now() < deadline {
+            for event in self.poll()? {
+                if let EventKind::Close = event.kind {
+                    self.remove(event.token);
+                }
+            }
+        }
+
+        log::info!("closing {} remaining connections", self.ws.len());
+        self.ws.clear();
+
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_timeout(timeout);
+        }
+        Ok(())
+    }
+
+    fn close(&mut self, token: Token, code: CloseCode, reason: &str) {
+        if self.ws.get(&token).is_none_or(|c| c.closing) {
+            return
+        }
+
+        log::debug!("closing {}: {}", token.0, reason);
+        self.push(token, Message::Close(Some(CloseFrame {
+            code,
+            reason: reason.to_string().into(),
+        })));
+        if let Some(connection) = self.ws.get_mut(&token) {
+            connection.closing = true;
+        }
+    }
+
+    fn respond(&mut self, token: Token, status: u16, reason: &str, content_type: &str, body: &str) {
+        if let Some(response) = self.http.remove(&token) {
+            let _ = response.send(admin::response(status, reason, content_type, body));
+        }
+    }
+
+    fn connections(&self) -> usize {
+        self.ws.len()
+    }
+
+    fn address(&self, token: Token) -> Option<IpAddr> {
+        self.ws.get(&token).map(|connection| connection.ip)
+    }
+
+    fn remove(&mut self, token: Token) {
+        log::debug!("removing socket {}", token.0);
+        // dropping the sender ends the connection task
+        self.ws.remove(&token);
+        self.http.remove(&token);
+    }
+
+    fn push(&mut self, token: Token, response: Message) {
+        log::debug!("push: {:?}", response);
+
+        let connection = match self.ws.get_mut(&token) {
+            Some(connection) => connection,
+            None => return
+        };
+
+        if connection.closing {
+            log::debug!("dropping response to closing {}", token.0);
+            return
+        }
+
+        if let Err(mpsc::error::TrySendError::Full(_)) = connection.outbound.try_send(response) {
+            log::warn!("outbound queue full on {}, disconnecting slow consumer", token.0);
+            connection.closing = true;
+            let _ = self.sender.send(Input::Event(Event{
+                token,
+                kind: EventKind::Close
+            }));
+        }
+    }
+
+}
+
+async fn accept(listener: TcpListener, shared: Arc<Shared>, mut stopped: watch::Receiver<bool>, kind: Listener) {
+    loop {
+        tokio::select! {
+            result = listener.accept() => match result {
+                Ok((socket, addr)) => {
+                    let token = shared.token();
+                    log::debug!("new connection {} from {}", token.0, addr);
+                    match kind {
+                        Listener::Ws => tokio::spawn(connection(token, socket, addr.ip(), shared.clone())),
+                        Listener::Admin => tokio::spawn(http(token, socket, shared.clone())),
+                        Listener::Tcp => tokio::spawn(tcp(token, socket, addr.ip(), shared.clone())),
+                    };
+                },
+                Err(e) => log::error!("accept error: {}", e)
+            },
+            _ = stopped.changed() => return
+        }
+    }
+}
+
+async fn signals(shared: Arc<Shared>) {
+    let (mut term, mut int) = match (signal(SignalKind::terminate()), signal(SignalKind::interrupt())) {
+        (Ok(term), Ok(int)) => (term, int),
+        _ => {
+            log::error!("could not register signal handlers");
+            return
+        }
+    };
+
+    loop {
+        let signal = tokio::select! {
+            _ = term.recv() => SIGTERM,
+            _ = int.recv() => SIGINT,
+        };
+        log::info!("received signal {}", signal);
+        shared.event(SIGNAL, EventKind::Shutdown);
+    }
+}
+
+async fn http(token: Token, mut socket: TcpStream, shared: Arc<Shared>) {
+    let timeout = shared.config.handshake_timeout;
+    let mut input = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    let read = async {
+        loop {
+            let n = socket.read(&mut buf).await?;
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into())
+            }
+
+            input.extend_from_slice(&buf[..n]);
+            if input.len() > admin::MAX_REQUEST {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "request too large"))
+            }
+
+            if let Some(path) = admin::parse(&input)? {
+                return Ok(path)
+            }
+        }
+    };
+
+    let path = match time::timeout(timeout, read).await {
+        Ok(Ok(path)) => path,
+        Ok(Err(e)) => {
+            log::debug!("http read error on {}: {}", token.0, e);
+            return
+        },
+        Err(_) => {
+            log::info!("handshake timeout on {}", token.0);
+            return
+        }
+    };
+
+    let (sender, receiver) = oneshot::channel();
+    shared.send(Input::Open(token, Peer::Http(sender)));
+    shared.event(token, EventKind::Http(path));
+
+    if let Ok(Ok(response)) = time::timeout(timeout, receiver).await {
+        if let Err(e) = socket.write_all(&response).await {
+            log::debug!("http write error on {}: {}", token.0, e);
+        }
+        let _ = socket.shutdown().await;
+    }
+}
+
+async fn connection(token: Token, socket: TcpStream, ip: IpAddr, shared: Arc<Shared>) {
+    let refuse = shared.admit(ip);
+
+    match &shared.acceptor {
+        Some(acceptor) => {
+            match time::timeout(shared.config.handshake_timeout, acceptor.accept(socket)).await {
+                Ok(Ok(stream)) => websocket(token, stream, ip, refuse, &shared).await,
+                Ok(Err(e)) => log::info!("tls handshake error on {}: {}", token.0, e),
+                Err(_) => log::info!("handshake timeout on {}", token.0)
+            }
+        },
+        None => websocket(token, socket, ip, refuse, &shared).await
+    }
+
+    shared.release(ip);
+}
+
+async fn tcp(token: Token, mut socket: TcpStream, ip: IpAddr, shared: Arc<Shared>) {
+    match shared.admit(ip) {
+        Some(reason) => {
+            log::info!("refusing tcp connection from {}: {}", ip, reason);
+            let _ = socket.write_all(&line::refused(reason)).await;
+            let _ = socket.shutdown().await;
+        },
+        None => lines(token, socket, ip, &shared).await
+    }
+
+    shared.release(ip);
+}
+
+async fn lines(token: Token, socket: TcpStream, ip: IpAddr, shared: &Shared) {
+    let config = &shared.config;
+    let (mut reader, mut writer) = socket.into_split();
+
+    let (outbound, mut queue) = mpsc::channel(config.max_queue);
+    shared.send(Input::Open(token, Peer::Ws(outbound, ip)));
+
+    let mut ticker = time::interval(TICK);
+    let mut seen = Instant::now();
+    let mut input = Vec::new();
+    let mut buf = [0u8; 4096];
+
+    loop {
+        tokio::select! {
+            result = reader.read(&mut buf) => match result {
+                Ok(0) => {
+                    log::debug!("connection closed on {}", token.0);
+                    break
+                },
+                Ok(n) => {
+                    seen = Instant::now();
+                    input.extend_from_slice(&buf[..n]);
+
+                    let lines = match line::split(&mut input, config.max_message_size) {
+                        Ok(lines) => lines,
+                        Err(e) => {
+                            log::info!("closing {}: {}", token.0, e);
+                            break
+                        }
+                    };
+
+                    for text in lines {
+                        // empty lines keep the connection alive
+                        if text.trim().is_empty() {
+                            continue
+                        }
+
+                        shared.event(token, transport::parse(&text));
+                    }
+                },
+                Err(error) => {
+                    log::error!("read error: {}", error);
+                    break
+                }
+            },
+            response = queue.recv() => match response {
+                // line connections have no close frame, queued lines were written already
+                Some(Message::Close(_)) => {
+                    let _ = writer.shutdown().await;
+                    break
+                },
+                Some(response) => {
+                    if let Some(line) = line::frame(&response) {
+                        if let Err(error) = writer.write_all(&line).await {
+                            log::debug!("write error on {}: {}", token.0, error);
+                            break
+                        }
+                    }
+                },
+                // removed by the server, nothing left to report
+                None => return
+            },
+            _ = ticker.tick() => {
+                if Instant::now().duration_since(seen) > config.idle_timeout {
+                    log::info!("closing idle connection {}", token.0);
+                    break
+                }
+            }
+        }
+    }
+
+    shared.event(token, EventKind::Close);
+}
+
+async fn websocket<S>(token: Token, stream: S, ip: IpAddr, refuse: Option<&'static str>, shared: &Shared)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let config = &shared.config;
+    let ws_config = config.websocket();
+    let check = Check::new(config.upgrade.clone(), refuse);
+    let handshake = tokio_tungstenite::accept_hdr_async_with_config(stream, check, Some(ws_config));
+
+    let mut ws = match time::timeout(config.handshake_timeout, handshake).await {
+        Ok(Ok(ws)) => ws,
+        Ok(Err(WsError::Http(response))) => {
+            log::info!("handshake refused on {}: {}", token.0, response.status());
+            return
+        },
+        Ok(Err(error)) => {
+            log::error!("handshake error: {}", error);
+            return
+        },
+        Err(_) => {
+            log::info!("handshake timeout on {}", token.0);
+            return
+        }
+    };
+    log::debug!("handshake done on socket {}", token.0);
+
+    let (outbound, mut queue) = mpsc::channel(config.max_queue);
+    shared.send(Input::Open(token, Peer::Ws(outbound, ip)));
+
+    let mut ticker = time::interval(TICK);
+    let mut seen = Instant::now();
+    let mut ping = Instant::now();
+    let mut closing = None;
+
+    loop {
+        tokio::select! {
+            message = ws.next() => match message {
+                Some(Ok(message)) => {
+                    seen = Instant::now();
+                    match message {
+                        Message::Text(_) if closing.is_some() => {
+                            log::debug!("ignoring request on closing {}", token.0);
+                        },
+                        Message::Text(msg) => shared.event(token, transport::parse(&msg)),
+                        Message::Close(_) => {
+                            let _ = ws.flush().await;
+                            break
+                        },
+                        _ => {}
+                    }
+                },
+                Some(Err(WsError::Capacity(e))) => {
+                    log::info!("closing {}: {}", token.0, e);
+                    let _ = ws.close(Some(CloseFrame {
+                        code: CloseCode::Size,
+                        reason: "message too big".into(),
+                    })).await;
+                    break
+                },
+                Some(Err(WsError::ConnectionClosed)) | None => {
+                    log::debug!("connection closed on {}", token.0);
+                    break
+                },
+                Some(Err(error)) => {
+                    log::error!("read error: {}", error);
+                    break
+                }
+            },
+            response = queue.recv() => match response {
+                Some(response) => {
+                    if let Message::Close(_) = response {
+                        closing = Some(Instant::now());
+                    }
+                    if let Err(error) = ws.send(response).await {
+                        log::debug!("write error on {}: {}", token.0, error);
+                        break
+                    }
+                },
+                // removed by the server, nothing left to report
+                None => return
+            },
+            _ = ticker.tick() => {
+                let now = Instant::now();
+
+                if closing.is_some_and(|since: Instant| now.duration_since(since) > config.handshake_timeout) {
+                    log::info!("close timeout on {}", token.0);
+                    break
+                } else if now.duration_since(seen) > config.idle_timeout {
+                    log::info!("closing idle connection {}", token.0);
+                    break
+                } else if now.duration_since(ping) >= config.ping_interval {
+                    ping = now;
+                    if ws.send(Message::Ping(Vec::new())).await.is_err() {
+                        break
+                    }
+                }
+            }
+        }
+    }
+
+    shared.event(token, EventKind::Close);
+}
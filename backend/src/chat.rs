@@ -0,0 +1,23 @@
+use serde::Deserialize;
+use serde::Serialize;
+use codename_engine::team::Team;
+
+pub const MAX_LENGTH: usize = 500;
+pub const HISTORY: usize = 50;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all="lowercase")]
+pub enum Channel {
+    #[default]
+    All,
+    Team,
+    Master
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Message {
+    pub channel: Channel,
+    pub name: String,
+    pub team: Option<Team>,
+    pub message: String,
+}
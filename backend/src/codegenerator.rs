@@ -0,0 +1,48 @@
+use rand::prelude::*;
+use std::collections::HashSet;
+
+// letters that are easy to read aloud without being confused (no I, L, O, U)
+const ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTVWXYZ";
+const MIN_LENGTH: usize = 4;
+const MAX_LENGTH: usize = 6;
+const ATTEMPTS: usize = 16;
+
+pub struct CodeGenerator {
+    used: HashSet<String>
+}
+
+impl CodeGenerator {
+
+    pub fn new() -> CodeGenerator {
+        CodeGenerator {
+            used: HashSet::new()
+        }
+    }
+
+    pub fn next(&mut self) -> Option<String> {
+        let mut rng = rand::thread_rng();
+
+        for length in MIN_LENGTH..=MAX_LENGTH {
+            for _ in 0..ATTEMPTS {
+                let code: String = (0..length)
+                    .map(|_| *ALPHABET.choose(&mut rng).unwrap() as char)
+                    .collect();
+
+                if self.used.insert(code.clone()) {
+                    return Some(code)
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn recycle(&mut self, code: &str) {
+        self.used.remove(code);
+    }
+
+}
+
+pub fn normalize(code: &str) -> String {
+    code.trim().to_uppercase()
+}
@@ -0,0 +1,327 @@
+use serde::Deserialize;
+use clap::{App, Arg, ArgMatches};
+use crate::error::ConfigError;
+use crate::server;
+use crate::stream;
+use crate::socket::Tls;
+use crate::upgrade::Upgrade;
+use crate::ratelimit;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Runtime {
+    Mio,
+    Tokio,
+}
+
+impl FromStr for Runtime {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Runtime, ()> {
+        match s {
+            "mio" => Ok(Runtime::Mio),
+            "tokio" => Ok(Runtime::Tokio),
+            _ => Err(())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: String,
+    pub runtime: Runtime,
+    pub admin: Option<SocketAddr>,
+    pub tcp: Option<SocketAddr>,
+    pub boards: Option<PathBuf>,
+    pub embeddings: Option<PathBuf>,
+    pub snapshot: Option<PathBuf>,
+    pub log: String,
+    pub restart_eta: Option<u64>,
+    pub workers: Option<usize>,
+    pub limits: Limits,
+    pub timeouts: Timeouts,
+    pub websocket: Upgrade,
+    pub rate_limit: ratelimit::Config,
+    pub tls: Option<Tls>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    pub max_rooms: Option<usize>,
+    // counted per address over an hour rather than per connection, which can
+    // only hold one room at a time and reconnect at will
+    pub max_rooms_per_ip: Option<usize>,
+    pub max_players: Option<usize>,
+    pub max_connections: Option<usize>,
+    pub max_connections_per_ip: Option<usize>,
+    pub max_message_size: usize,
+    pub max_queue: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    pub ping: u64,
+    pub idle: u64,
+    pub handshake: u64,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            bind: "0.0.0.0:8080".to_string(),
+            runtime: Runtime::Mio,
+            admin: None,
+            tcp: None,
+            boards: None,
+            embeddings: None,
+            snapshot: None,
+            log: "info".to_string(),
+            restart_eta: None,
+            workers: None,
+            limits: Limits::default(),
+            timeouts: Timeouts::default(),
+            websocket: Upgrade::default(),
+            rate_limit: ratelimit::Config::default(),
+            tls: None,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        let stream = stream::Config::default();
+        Limits {
+            max_rooms: None,
+            max_rooms_per_ip: None,
+            max_players: None,
+            max_connections: None,
+            max_connections_per_ip: None,
+            max_message_size: stream.max_message_size,
+            max_queue: stream.max_queue,
+        }
+    }
+}
+
+impl Default for Timeouts {
+    fn default() -> Timeouts {
+        let stream = stream::Config::default();
+        Timeouts {
+            ping: stream.ping_interval.as_secs(),
+            idle: stream.idle_timeout.as_secs(),
+            handshake: stream.handshake_timeout.as_secs(),
+        }
+    }
+}
+
+impl Config {
+
+    pub fn load() -> Result<Config, ConfigError> {
+        let matches = app().get_matches();
+
+        let mut config = match matches.value_of("config") {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default()
+        };
+
+        config.apply(&matches)?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &str) -> Result<Config, ConfigError> {
+        let contents = fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    fn apply(&mut self, matches: &ArgMatches) -> Result<(), ConfigError> {
+        if let Some(bind) = matches.value_of("bind") {
+            self.bind = bind.to_string();
+        }
+        if let Some(runtime) = parse(matches, "runtime")? {
+            self.runtime = runtime;
+        }
+        if let Some(admin) = parse(matches, "admin")? {
+            self.admin = Some(admin);
+        }
+        if let Some(tcp) = parse(matches, "tcp")? {
+            self.tcp = Some(tcp);
+        }
+        if let Some(boards) = matches.value_of("boards") {
+            self.boards = Some(boards.into());
+        }
+        if let Some(embeddings) = matches.value_of("embeddings") {
+            self.embeddings = Some(embeddings.into());
+        }
+        if let Some(snapshot) = matches.value_of("snapshot") {
+            self.snapshot = Some(snapshot.into());
+        }
+        if let Some(log) = matches.value_of("log") {
+            self.log = log.to_string();
+        }
+
+        if let Some(eta) = parse(matches, "restart-eta")? {
+            self.restart_eta = Some(eta);
+        }
+
+        if let Some(workers) = parse(matches, "workers")? {
+            self.workers = Some(workers);
+        }
+
+        if let Some(max) = parse(matches, "max-rooms")? {
+            self.limits.max_rooms = Some(max);
+        }
+        if let Some(max) = parse(matches, "max-rooms-per-ip")? {
+            self.limits.max_rooms_per_ip = Some(max);
+        }
+        if let Some(max) = parse(matches, "max-players")? {
+            self.limits.max_players = Some(max);
+        }
+        if let Some(max) = parse(matches, "max-connections")? {
+            self.limits.max_connections = Some(max);
+        }
+        if let Some(max) = parse(matches, "max-connections-per-ip")? {
+            self.limits.max_connections_per_ip = Some(max);
+        }
+        if let Some(max) = parse(matches, "max-message-size")? {
+            self.limits.max_message_size = max;
+        }
+        if let Some(max) = parse(matches, "max-queue")? {
+            self.limits.max_queue = max;
+        }
+
+        if let Some(secs) = parse(matches, "ping-interval")? {
+            self.timeouts.ping = secs;
+        }
+        if let Some(secs) = parse(matches, "idle-timeout")? {
+            self.timeouts.idle = secs;
+        }
+        if let Some(secs) = parse(matches, "handshake-timeout")? {
+            self.timeouts.handshake = secs;
+        }
+
+        if let Some(path) = matches.value_of("path") {
+            self.websocket.path = Some(path.to_string());
+        }
+        if let Some(origins) = matches.values_of("origin") {
+            self.websocket.origins = origins.map(String::from).collect();
+        }
+        if let Some(protocols) = matches.values_of("protocol") {
+            self.websocket.protocols = protocols.map(String::from).collect();
+        }
+
+        match (matches.value_of("tls-certificate"), matches.value_of("tls-key")) {
+            (Some(certificate), Some(key)) => {
+                self.tls = Some(Tls {
+                    certificate: certificate.into(),
+                    key: key.into(),
+                });
+            },
+            (None, None) => {},
+            _ => return Err(ConfigError::Invalid("tls certificate and key must be given together".to_string()))
+        }
+
+        Ok(())
+    }
+
+    pub fn server(&self) -> server::Config {
+        server::Config {
+            max_rooms: self.limits.max_rooms,
+            max_players: self.limits.max_players,
+            max_rooms_per_ip: self.limits.max_rooms_per_ip,
+            restart_eta: self.restart_eta,
+            rate_limit: self.rate_limit.clone(),
+            workers: self.workers.unwrap_or_else(|| {
+                thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+            }),
+            // loaded separately, see load_embeddings_file
+            spymaster: None,
+            snapshot: self.snapshot.clone(),
+        }
+    }
+
+    pub fn stream(&self) -> stream::Config {
+        stream::Config {
+            ping_interval: Duration::from_secs(self.timeouts.ping),
+            idle_timeout: Duration::from_secs(self.timeouts.idle),
+            handshake_timeout: Duration::from_secs(self.timeouts.handshake),
+            max_queue: self.limits.max_queue,
+            max_message_size: self.limits.max_message_size,
+            max_connections: self.limits.max_connections,
+            max_connections_per_ip: self.limits.max_connections_per_ip,
+            upgrade: self.websocket.clone(),
+            tls: self.tls.clone(),
+            admin: self.admin,
+            tcp: self.tcp,
+        }
+    }
+
+}
+
+fn parse<T: FromStr>(matches: &ArgMatches, name: &str) -> Result<Option<T>, ConfigError> {
+    match matches.value_of(name) {
+        Some(value) => value.parse()
+            .map(Some)
+            .map_err(|_| ConfigError::Invalid(format!("{}: {}", name, value))),
+        None => Ok(None)
+    }
+}
+
+fn option<'a>(name: &'a str, env: &'a str, help: &'a str) -> Arg<'a, 'a> {
+    Arg::with_name(name)
+        .long(name)
+        .env(env)
+        .takes_value(true)
+        .help(help)
+}
+
+fn app<'a>() -> App<'a, 'a> {
+    App::new("codename")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Codenames game server")
+        .arg(Arg::with_name("boards")
+            .index(1)
+            .env("CODENAME_BOARDS")
+            .help("board set file (words and tile maps)"))
+        .arg(option("config", "CODENAME_CONFIG", "TOML configuration file")
+            .short("c"))
+        .arg(option("bind", "CODENAME_BIND", "address to listen on")
+            .short("b"))
+        .arg(option("runtime", "CODENAME_RUNTIME", "transport runtime, mio or tokio (requires the async feature)")
+            .possible_values(&["mio", "tokio"]))
+        .arg(option("admin", "CODENAME_ADMIN", "address serving /health and /metrics over plain HTTP"))
+        .arg(option("tcp", "CODENAME_TCP", "address accepting newline-delimited JSON requests over plain TCP"))
+        .arg(option("embeddings", "CODENAME_EMBEDDINGS", "word vectors in word2vec or GloVe text format, enables the spymaster bot"))
+        .arg(option("snapshot", "CODENAME_SNAPSHOT", "file the rooms are written to as JSON on shutdown"))
+        .arg(option("log", "CODENAME_LOG", "log filter, overridden by RUST_LOG"))
+        .arg(option("restart-eta", "CODENAME_RESTART_ETA", "seconds until restart announced to players on shutdown"))
+        .arg(option("workers", "CODENAME_WORKERS", "number of room worker threads, defaults to the number of CPUs"))
+        .arg(option("max-rooms", "CODENAME_MAX_ROOMS", "maximum number of rooms"))
+        .arg(option("max-rooms-per-ip", "CODENAME_MAX_ROOMS_PER_IP", "maximum number of rooms created from one address per hour"))
+        .arg(option("max-players", "CODENAME_MAX_PLAYERS", "maximum number of players per room"))
+        .arg(option("max-connections", "CODENAME_MAX_CONNECTIONS", "maximum number of open connections"))
+        .arg(option("max-connections-per-ip", "CODENAME_MAX_CONNECTIONS_PER_IP", "maximum number of open connections per address"))
+        .arg(option("max-message-size", "CODENAME_MAX_MESSAGE_SIZE", "maximum size of an inbound message in bytes"))
+        .arg(option("max-queue", "CODENAME_MAX_QUEUE", "maximum number of outbound messages queued per connection"))
+        .arg(option("ping-interval", "CODENAME_PING_INTERVAL", "seconds between pings to idle connections"))
+        .arg(option("idle-timeout", "CODENAME_IDLE_TIMEOUT", "seconds before an idle connection is closed"))
+        .arg(option("handshake-timeout", "CODENAME_HANDSHAKE_TIMEOUT", "seconds allowed to complete the handshake"))
+        .arg(option("path", "CODENAME_PATH", "WebSocket endpoint path"))
+        .arg(option("origin", "CODENAME_ORIGINS", "allowed Origin header, may be repeated")
+            .multiple(true)
+            .number_of_values(1)
+            .use_delimiter(true))
+        .arg(option("protocol", "CODENAME_PROTOCOLS", "supported WebSocket subprotocol, may be repeated")
+            .multiple(true)
+            .number_of_values(1)
+            .use_delimiter(true))
+        .arg(option("tls-certificate", "CODENAME_TLS_CERTIFICATE", "PEM certificate to serve wss:// directly"))
+        .arg(option("tls-key", "CODENAME_TLS_KEY", "PEM private key (PKCS#8) for the certificate"))
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
+use codename_engine::error::{BoardError, GameError, ValidationError};
 use serde_json::error::Error as SerdeError;
+use std::io;
 use uuid::Uuid;
 
 #[derive(Error, Debug)]
@@ -12,32 +14,8 @@ pub enum RequestError {
     Unknown(String),
     #[error("invalid value: {0}")]
     Invalid(&'static str),
-}
-
-#[derive(Error, Debug)]
-pub enum GameError {
-    #[error("{0} not found")]
-    NotFound(&'static str),
-    #[error("not your turn to give a {0}")]
-    Turn(&'static str),
-    #[error("player is not a master")]
-    NotMaster,
-    #[error("player is not the admin")]
-    NotAdmin,
-    #[error("team {0} does not have enough players")]
-    MissingPlayers(&'static str),
-    #[error("game has not started")]
-    NotStarted,
-    #[error("game has already started")]
-    AlreadyStarted,
-    #[error("card has already been set")]
-    CardSet,
-}
-
-#[derive(Error, Debug)]
-pub enum BoardError {
-    #[error("language '{0}' not found")]
-    Language(String),
+    #[error("rate limit exceeded for {0} requests")]
+    Throttled(&'static str),
 }
 
 #[derive(Error, Debug)]
@@ -52,5 +30,65 @@ pub enum RoomError {
     Forbidden,
     #[error("room {0} not found")]
     NotFound(Uuid),
+    #[error("no room code available")]
+    Code,
+    #[error("invalid room password")]
+    Password,
+    #[error("too many {0}")]
+    Limit(&'static str),
+    #[error("spymaster bot unavailable: {0}")]
+    Bot(&'static str),
+}
+
+impl From<ValidationError> for RequestError {
+    fn from(error: ValidationError) -> RequestError {
+        match error {
+            ValidationError::Missing(field) => RequestError::Missing(field),
+            ValidationError::Invalid(reason) => RequestError::Invalid(reason),
+        }
+    }
+}
+
+impl RequestError {
 
+    pub fn variant(&self) -> &'static str {
+        match self {
+            RequestError::Parse(_) => "request_parse",
+            RequestError::Missing(_) => "request_missing",
+            RequestError::Unknown(_) => "request_unknown",
+            RequestError::Invalid(_) => "request_invalid",
+            RequestError::Throttled(_) => "request_throttled",
+        }
+    }
+
+}
+
+impl RoomError {
+
+    pub fn variant(&self) -> &'static str {
+        match self {
+            RoomError::Board(BoardError::Language(_)) => "board_language",
+            RoomError::Game(e) => e.variant(),
+            RoomError::Unhandled => "room_unhandled",
+            RoomError::Forbidden => "room_forbidden",
+            RoomError::NotFound(_) => "room_not_found",
+            RoomError::Code => "room_code",
+            RoomError::Password => "room_password",
+            RoomError::Limit(_) => "room_limit",
+            RoomError::Bot(_) => "room_bot",
+        }
+    }
+
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("unable to read configuration: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid configuration file: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("missing option: {0}")]
+    Missing(&'static str),
+    #[error("invalid option: {0}")]
+    Invalid(String),
 }
@@ -0,0 +1,24 @@
+pub mod request;
+pub mod response;
+pub mod server;
+pub mod room;
+mod idgenerator;
+mod codegenerator;
+mod password;
+pub mod chat;
+pub mod stream;
+pub mod transport;
+pub mod memory;
+#[cfg(feature = "async")]
+pub mod asyncstream;
+pub mod upgrade;
+pub mod socket;
+pub mod config;
+mod admin;
+mod line;
+mod metrics;
+pub mod ratelimit;
+mod worker;
+pub mod error;
+
+extern crate log;
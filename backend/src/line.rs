@@ -0,0 +1,134 @@
+use crate::transport;
+use crate::response;
+use mio::net::TcpStream;
+use tungstenite::Message;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+pub struct Connection {
+    pub socket: TcpStream,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    written: usize,
+    // where each line not fully written yet ends in output
+    ends: VecDeque<usize>,
+    max_line: usize,
+    eof: bool,
+}
+
+impl Connection {
+
+    pub fn new(socket: TcpStream, max_line: usize) -> Connection {
+        Connection {
+            socket,
+            input: Vec::new(),
+            output: Vec::new(),
+            written: 0,
+            ends: VecDeque::new(),
+            max_line,
+            eof: false,
+        }
+    }
+
+    // returns the complete lines received so far, empty lines included,
+    // lines sent along with the end of the stream are returned before eof is set
+    pub fn read(&mut self) -> io::Result<Vec<String>> {
+        let mut buf = [0u8; 4096];
+        let mut lines = Vec::new();
+
+        loop {
+            match self.socket.read(&mut buf) {
+                Ok(0) => {
+                    self.eof = true;
+                    return Ok(lines)
+                },
+                Ok(n) => self.input.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(lines),
+                Err(e) => return Err(e)
+            }
+
+            lines.extend(split(&mut self.input, self.max_line)?);
+        }
+    }
+
+    pub fn eof(&self) -> bool {
+        self.eof
+    }
+
+    pub fn queue(&mut self, message: &Message) {
+        if let Some(line) = frame(message) {
+            self.output.extend_from_slice(&line);
+            self.ends.push_back(self.output.len());
+        }
+    }
+
+    // number of lines waiting to be written
+    pub fn queued(&self) -> usize {
+        self.ends.len()
+    }
+
+    // returns true once everything queued has been written
+    pub fn write(&mut self) -> io::Result<bool> {
+        while self.written < self.output.len() {
+            match self.socket.write(&self.output[self.written..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => self.written += n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(e) => return Err(e)
+            }
+
+            while let Some(end) = self.ends.front() {
+                if *end > self.written {
+                    break
+                }
+                self.ends.pop_front();
+            }
+        }
+
+        self.output.clear();
+        self.written = 0;
+        self.ends.clear();
+        Ok(true)
+    }
+
+}
+
+// removes the complete lines from the input buffer
+pub fn split(input: &mut Vec<u8>, max_line: usize) -> io::Result<Vec<String>> {
+    let mut lines = Vec::new();
+
+    while let Some(end) = input.iter().position(|b| *b == b'\n') {
+        if end > max_line {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"))
+        }
+
+        let line: Vec<u8> = input.drain(..=end).collect();
+        let line = String::from_utf8(line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        lines.push(line.trim_end_matches(&['\r', '\n'][..]).to_string());
+    }
+
+    if input.len() > max_line {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"))
+    }
+
+    Ok(lines)
+}
+
+// the line sent for a message, only text messages have one
+pub fn frame(message: &Message) -> Option<Vec<u8>> {
+    match message {
+        Message::Text(text) => {
+            let mut line = text.clone().into_bytes();
+            line.push(b'\n');
+            Some(line)
+        },
+        _ => None
+    }
+}
+
+pub fn refused(reason: &str) -> Vec<u8> {
+    transport::encode(&response::error(reason))
+        .and_then(|message| frame(&message))
+        .unwrap_or_default()
+}
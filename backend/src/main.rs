@@ -1,34 +1,63 @@
-mod request;
-mod response;
-mod game; 
-mod gameteam;
-mod server;
-mod team;
-mod board;
-mod room;
-mod idgenerator;
-mod stream;
-mod error;
-
-extern crate log;
-
 use mio::net::TcpListener;
+use codename::{config, error, server, stream};
+#[cfg(feature = "async")]
+use codename::asyncstream;
+use codename::transport::Transport;
+use codename_engine::board::BoardSet;
+use std::net::SocketAddr;
 use std::error::Error;
-use std::env;
-use std::rc::Rc;
+use std::sync::Arc;
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let config = config::Config::load()?;
+
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(&config.log)).init();
+
+    let boards = config.boards.as_ref().ok_or(error::ConfigError::Missing("boards"))?;
+    let boardset = codename_engine::board::load_board_file(&boards.to_string_lossy())?;
 
-fn main() -> Result<(), Box<dyn Error>> {
-    env_logger::init();
+    let addr = config.bind.parse()?;
 
-    let args: Vec<String> = env::args().collect();
-    let boardset = board::load_board_file(&args[1])?;
+    match config.runtime {
+        config::Runtime::Mio => {
+            let listener = TcpListener::bind(addr)?;
+            log::info!("listening on {}", addr);
 
+            let stream = stream::Stream::new(listener, config.stream())?;
+            serve(boardset, stream, &config)
+        },
+        config::Runtime::Tokio => serve_async(boardset, addr, &config)
+    }
+}
+
+#[cfg(feature = "async")]
+fn serve_async(boardset: BoardSet, addr: SocketAddr, config: &config::Config) -> Result<(), Box<dyn Error>> {
+    let stream = asyncstream::AsyncStream::new(addr, config.stream())?;
+    log::info!("listening on {} (tokio)", addr);
+    serve(boardset, stream, config)
+}
 
-    let addr = "0.0.0.0:8080".parse()?;
-    let listener = TcpListener::bind(addr)?;
+#[cfg(not(feature = "async"))]
+fn serve_async(_: BoardSet, _: SocketAddr, _: &config::Config) -> Result<(), Box<dyn Error>> {
+    Err(error::ConfigError::Invalid("the tokio runtime requires the async feature".to_string()).into())
+}
+
+fn serve<T: Transport>(boardset: BoardSet, stream: T, config: &config::Config) -> Result<(), Box<dyn Error>> {
+    let mut server_config = config.server();
+    if let Some(path) = &config.embeddings {
+        let spymaster = codename_engine::spymaster::load_embeddings_file(&path.to_string_lossy())?;
+        log::info!("spymaster bot knows {} words", spymaster.len());
+        server_config.spymaster = Some(Arc::new(spymaster));
+    }
 
-    let stream = stream::Stream::new(listener)?;
-    let mut server = server::Server::new(Rc::new(boardset), stream);
+    let mut server = server::Server::new(Arc::new(boardset), stream, server_config)?;
 
     if let Err(e) = server.run() {
         log::error!("server error: {}", e);
@@ -0,0 +1,244 @@
+use crate::idgenerator::IdGenerator;
+use crate::transport::{self, Transport, Event, EventKind, Waker};
+use crate::request::Request;
+use crate::response::{Envelope, Response};
+use mio::Token;
+use tungstenite::Message;
+use tungstenite::protocol::frame::coding::CloseCode;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::sync::mpsc::{self, Receiver, Sender, RecvTimeoutError};
+use std::time::Duration;
+
+// what a connection receives from the server, responses are kept typed
+#[derive(Debug)]
+pub enum Output {
+    Response(Response),
+    Message(Message),
+    Http(u16, String),
+    Close(CloseCode, String),
+}
+
+enum Input {
+    Connect(Token, Sender<Output>),
+    Event(Event),
+    Wake,
+}
+
+struct Wake(Sender<Input>);
+
+impl Waker for Wake {
+    fn wake(&self) -> io::Result<()> {
+        self.0.send(Input::Wake)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "transport is gone"))
+    }
+}
+
+// an in-process transport where connections are channels, for tests and fuzzing
+pub struct Memory {
+    input: Receiver<Input>,
+    sender: Sender<Input>,
+    // tokens are recycled as by the socket transport, so tests see them reused
+    generator: Arc<Mutex<IdGenerator>>,
+    clients: HashMap<Token, Sender<Output>>,
+    retired: HashSet<Token>,
+    pending: Vec<Event>,
+}
+
+// opens connections to a Memory transport owned by a server
+#[derive(Clone)]
+pub struct Handle {
+    sender: Sender<Input>,
+    generator: Arc<Mutex<IdGenerator>>,
+}
+
+pub struct Connection {
+    pub token: Token,
+    sender: Sender<Input>,
+    output: Receiver<Output>,
+}
+
+impl Memory {
+
+    pub fn new() -> Memory {
+        let (sender, input) = mpsc::channel();
+
+        Memory {
+            input,
+            sender,
+            generator: Arc::new(Mutex::new(IdGenerator::new())),
+            clients: HashMap::new(),
+            retired: HashSet::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn handle(&self) -> Handle {
+        Handle {
+            sender: self.sender.clone(),
+            generator: self.generator.clone(),
+        }
+    }
+
+    fn handle_input(&mut self, input: Input, events: &mut Vec<Event>) {
+        match input {
+            Input::Connect(token, sender) => {
+                self.clients.insert(token, sender);
+            },
+            Input::Event(event) => events.push(event),
+            Input::Wake => {}
+        }
+    }
+
+    fn output(&mut self, token: Token, output: Output) {
+        if let Some(client) = self.clients.get(&token) {
+            if client.send(output).is_err() {
+                // the client end was dropped without closing
+                self.clients.remove(&token);
+                self.retired.insert(token);
+                self.pending.push(Event { token, kind: EventKind::Close });
+            }
+        }
+    }
+
+}
+
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory::new()
+    }
+}
+
+impl Transport for Memory {
+
+    fn waker(&self) -> io::Result<Arc<dyn Waker>> {
+        Ok(Arc::new(Wake(self.sender.clone())))
+    }
+
+    fn poll(&mut self) -> io::Result<Vec<Event>> {
+        let mut events = std::mem::take(&mut self.pending);
+
+        if events.is_empty() {
+            // the transport holds a sender, so this only fails if it is gone
+            let input = self.input.recv()
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "transport stopped"))?;
+            self.handle_input(input, &mut events);
+        }
+
+        while let Ok(input) = self.input.try_recv() {
+            self.handle_input(input, &mut events);
+        }
+
+        Ok(events)
+    }
+
+    fn send(&mut self, envelope: Envelope) {
+        for token in envelope.recipients {
+            self.output(token, Output::Response(envelope.response.clone()));
+        }
+    }
+
+    fn push(&mut self, token: Token, response: Message) {
+        self.output(token, Output::Message(response));
+    }
+
+    fn close(&mut self, token: Token, code: CloseCode, reason: &str) {
+        if self.clients.contains_key(&token) {
+            self.output(token, Output::Close(code, reason.to_string()));
+            if self.clients.remove(&token).is_some() {
+                self.retired.insert(token);
+            }
+            self.pending.push(Event { token, kind: EventKind::Close });
+        }
+    }
+
+    fn remove(&mut self, token: Token) {
+        // dropping the sender disconnects the client end
+        if self.clients.remove(&token).is_some() {
+            self.retired.insert(token);
+        }
+    }
+
+    fn release(&mut self, token: Token) {
+        if self.retired.remove(&token) {
+            self.generator.lock().unwrap_or_else(PoisonError::into_inner).recycle(token.0);
+        }
+    }
+
+    fn respond(&mut self, token: Token, status: u16, _: &str, _: &str, body: &str) {
+        self.output(token, Output::Http(status, body.to_string()));
+    }
+
+    fn connections(&self) -> usize {
+        self.clients.len()
+    }
+
+    // every connection comes from the same process
+    fn address(&self, token: Token) -> Option<IpAddr> {
+        self.clients.get(&token).map(|_| IpAddr::V4(Ipv4Addr::LOCALHOST))
+    }
+
+    fn shutdown(&mut self, _: Duration) -> io::Result<()> {
+        for (_, client) in self.clients.drain() {
+            let _ = client.send(Output::Close(CloseCode::Away, "server shutting down".to_string()));
+        }
+        self.pending.clear();
+        Ok(())
+    }
+
+}
+
+impl Handle {
+
+    pub fn connect(&self) -> Connection {
+        let token = Token(self.generator.lock().unwrap_or_else(PoisonError::into_inner).next());
+        let (sender, output) = mpsc::channel();
+        let _ = self.sender.send(Input::Connect(token, sender));
+
+        Connection {
+            token,
+            sender: self.sender.clone(),
+            output,
+        }
+    }
+
+    pub fn shutdown(&self) {
+        let _ = self.sender.send(Input::Event(Event { token: Token(usize::MAX), kind: EventKind::Shutdown }));
+    }
+
+}
+
+impl Connection {
+
+    pub fn send(&self, request: Request) {
+        self.event(EventKind::Request(request));
+    }
+
+    // goes through the same parsing as text received by the other transports
+    pub fn send_text(&self, text: &str) {
+        self.event(transport::parse(text));
+    }
+
+    pub fn http(&self, path: &str) {
+        self.event(EventKind::Http(path.to_string()));
+    }
+
+    pub fn close(&self) {
+        self.event(EventKind::Close);
+    }
+
+    pub fn recv(&self, timeout: Duration) -> Result<Output, RecvTimeoutError> {
+        self.output.recv_timeout(timeout)
+    }
+
+    pub fn try_recv(&self) -> Option<Output> {
+        self.output.try_recv().ok()
+    }
+
+    fn event(&self, kind: EventKind) {
+        let _ = self.sender.send(Input::Event(Event { token: self.token, kind }));
+    }
+
+}
@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+const BUCKETS: [f64; 8] = [0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0];
+
+#[derive(Default)]
+pub struct Metrics {
+    pub games_started: u64,
+    pub games_finished: u64,
+    requests: BTreeMap<&'static str, u64>,
+    errors: BTreeMap<&'static str, u64>,
+    latency: [u64; BUCKETS.len()],
+    latency_count: u64,
+    latency_sum: f64,
+}
+
+impl Metrics {
+
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    pub fn request(&mut self, kind: &'static str) {
+        *self.requests.entry(kind).or_insert(0) += 1;
+    }
+
+    pub fn error(&mut self, variant: &'static str) {
+        *self.errors.entry(variant).or_insert(0) += 1;
+    }
+
+    pub fn observe(&mut self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+
+        for (bucket, count) in BUCKETS.iter().zip(self.latency.iter_mut()) {
+            if secs <= *bucket {
+                *count += 1;
+            }
+        }
+
+        self.latency_count += 1;
+        self.latency_sum += secs;
+    }
+
+    pub fn render(&self, connections: usize, rooms: &BTreeMap<&'static str, usize>) -> String {
+        let mut out = String::new();
+
+        gauge(&mut out, "codename_connections", "Open client connections");
+        let _ = writeln!(out, "codename_connections {}", connections);
+
+        gauge(&mut out, "codename_rooms", "Rooms by state");
+        for state in &["join", "team", "play", "end"] {
+            let _ = writeln!(out, "codename_rooms{{state=\"{}\"}} {}", state, rooms.get(state).unwrap_or(&0));
+        }
+
+        counter(&mut out, "codename_games_started_total", "Games started");
+        let _ = writeln!(out, "codename_games_started_total {}", self.games_started);
+
+        counter(&mut out, "codename_games_finished_total", "Games finished");
+        let _ = writeln!(out, "codename_games_finished_total {}", self.games_finished);
+
+        counter(&mut out, "codename_requests_total", "Requests by kind");
+        for (kind, count) in self.requests.iter() {
+            let _ = writeln!(out, "codename_requests_total{{kind=\"{}\"}} {}", kind, count);
+        }
+
+        counter(&mut out, "codename_errors_total", "Errors by variant");
+        for (variant, count) in self.errors.iter() {
+            let _ = writeln!(out, "codename_errors_total{{error=\"{}\"}} {}", variant, count);
+        }
+
+        let _ = writeln!(out, "# HELP codename_poll_duration_seconds Time spent handling the events of one poll");
+        let _ = writeln!(out, "# TYPE codename_poll_duration_seconds histogram");
+        for (bucket, count) in BUCKETS.iter().zip(self.latency.iter()) {
+            let _ = writeln!(out, "codename_poll_duration_seconds_bucket{{le=\"{}\"}} {}", bucket, count);
+        }
+        let _ = writeln!(out, "codename_poll_duration_seconds_bucket{{le=\"+Inf\"}} {}", self.latency_count);
+        let _ = writeln!(out, "codename_poll_duration_seconds_sum {}", self.latency_sum);
+        let _ = writeln!(out, "codename_poll_duration_seconds_count {}", self.latency_count);
+
+        out
+    }
+
+}
+
+fn gauge(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+}
+
+fn counter(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+}
@@ -0,0 +1,50 @@
+use hmac::Hmac;
+use rand::prelude::*;
+use sha2::Sha256;
+
+const SALT_LENGTH: usize = 16;
+const HASH_LENGTH: usize = 32;
+// PBKDF2-HMAC-SHA256 rounds, slow enough to make guessing a leaked hash costly
+const ROUNDS: u32 = 100_000;
+
+pub struct PasswordHash {
+    salt: [u8; SALT_LENGTH],
+    rounds: u32,
+    hash: [u8; HASH_LENGTH],
+}
+
+impl std::fmt::Debug for PasswordHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("PasswordHash")
+    }
+}
+
+impl PasswordHash {
+
+    pub fn new(password: &str) -> PasswordHash {
+        let mut salt = [0u8; SALT_LENGTH];
+        rand::thread_rng().fill(&mut salt);
+
+        PasswordHash {
+            salt,
+            rounds: ROUNDS,
+            hash: derive(&salt, ROUNDS, password),
+        }
+    }
+
+    pub fn verify(&self, password: &str) -> bool {
+        let hash = derive(&self.salt, self.rounds, password);
+
+        // compare every byte so that the time taken does not depend on the password
+        hash.iter()
+            .zip(self.hash.iter())
+            .fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+
+}
+
+fn derive(salt: &[u8], rounds: u32, password: &str) -> [u8; HASH_LENGTH] {
+    let mut hash = [0u8; HASH_LENGTH];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, rounds, &mut hash);
+    hash
+}
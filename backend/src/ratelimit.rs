@@ -0,0 +1,210 @@
+use serde::Deserialize;
+use mio::Token;
+use std::collections::HashMap;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limit {
+    pub rate: f64,
+    pub burst: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub enabled: bool,
+    pub default: Limit,
+    pub kinds: HashMap<String, Limit>,
+    pub max_violations: f64,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        let mut kinds = HashMap::new();
+        kinds.insert("chat".to_string(), Limit { rate: 1.0, burst: 5.0 });
+        kinds.insert("room".to_string(), Limit { rate: 0.2, burst: 2.0 });
+
+        Config {
+            enabled: true,
+            default: Limit { rate: 5.0, burst: 10.0 },
+            kinds,
+            max_violations: 20.0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Allow,
+    Throttle,
+    Disconnect,
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+
+    fn new(limit: &Limit, now: Instant) -> Bucket {
+        Bucket {
+            tokens: limit.burst,
+            last: now,
+        }
+    }
+
+    fn take(&mut self, limit: &Limit, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.rate).min(limit.burst);
+        self.last = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+}
+
+pub struct RateLimiter {
+    config: Config,
+    buckets: HashMap<Token, HashMap<&'static str, Bucket>>,
+    // refills at one violation per second, a connection running it dry is disconnected
+    violations: HashMap<Token, Bucket>,
+}
+
+impl RateLimiter {
+
+    pub fn new(config: Config) -> RateLimiter {
+        RateLimiter {
+            config,
+            buckets: HashMap::new(),
+            violations: HashMap::new(),
+        }
+    }
+
+    pub fn check(&mut self, token: Token, kind: &'static str) -> Verdict {
+        self.check_at(token, kind, Instant::now())
+    }
+
+    fn check_at(&mut self, token: Token, kind: &'static str, now: Instant) -> Verdict {
+        if !self.config.enabled {
+            return Verdict::Allow
+        }
+
+        let limit = self.config.kinds.get(kind).unwrap_or(&self.config.default);
+
+        let bucket = self.buckets
+            .entry(token)
+            .or_default()
+            .entry(kind)
+            .or_insert_with(|| Bucket::new(limit, now));
+
+        if bucket.take(limit, now) {
+            return Verdict::Allow
+        }
+
+        let abuse = Limit {
+            rate: 1.0,
+            burst: self.config.max_violations,
+        };
+        let violations = self.violations
+            .entry(token)
+            .or_insert_with(|| Bucket::new(&abuse, now));
+
+        if violations.take(&abuse, now) {
+            Verdict::Throttle
+        } else {
+            Verdict::Disconnect
+        }
+    }
+
+    pub fn remove(&mut self, token: Token) {
+        self.buckets.remove(&token);
+        self.violations.remove(&token);
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limiter(rate: f64, burst: f64, max_violations: f64) -> RateLimiter {
+        RateLimiter::new(Config {
+            enabled: true,
+            default: Limit { rate, burst },
+            kinds: HashMap::new(),
+            max_violations,
+        })
+    }
+
+    fn allowed(limiter: &mut RateLimiter, token: Token, now: Instant) -> usize {
+        (0..100)
+            .take_while(|_| limiter.check_at(token, "chat", now) == Verdict::Allow)
+            .count()
+    }
+
+    #[test]
+    fn burst() {
+        let mut limiter = limiter(1.0, 3.0, 100.0);
+        let now = Instant::now();
+
+        assert_eq!(allowed(&mut limiter, Token(1), now), 3);
+        // other connections and kinds have their own buckets
+        assert_eq!(allowed(&mut limiter, Token(2), now), 3);
+        assert_eq!(limiter.check_at(Token(1), "room", now), Verdict::Allow);
+    }
+
+    #[test]
+    fn refill() {
+        let mut limiter = limiter(2.0, 3.0, 100.0);
+        let now = Instant::now();
+
+        assert_eq!(allowed(&mut limiter, Token(1), now), 3);
+        assert_eq!(allowed(&mut limiter, Token(1), now + Duration::from_millis(750)), 1);
+        // never more than the burst, however long the connection waited
+        assert_eq!(allowed(&mut limiter, Token(1), now + Duration::from_secs(60)), 3);
+    }
+
+    #[test]
+    fn violations() {
+        let mut limiter = limiter(0.0, 1.0, 3.0);
+        let now = Instant::now();
+
+        assert_eq!(limiter.check_at(Token(1), "chat", now), Verdict::Allow);
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(Token(1), "chat", now), Verdict::Throttle);
+        }
+        assert_eq!(limiter.check_at(Token(1), "chat", now), Verdict::Disconnect);
+
+        // violations refill at one per second
+        let later = now + Duration::from_secs(1);
+        assert_eq!(limiter.check_at(Token(1), "chat", later), Verdict::Throttle);
+        assert_eq!(limiter.check_at(Token(1), "chat", later), Verdict::Disconnect);
+    }
+
+    #[test]
+    fn remove() {
+        let mut limiter = limiter(0.0, 1.0, 1.0);
+        let now = Instant::now();
+
+        assert_eq!(allowed(&mut limiter, Token(1), now), 1);
+        limiter.remove(Token(1));
+        assert_eq!(allowed(&mut limiter, Token(1), now), 1);
+    }
+
+    #[test]
+    fn disabled() {
+        let mut limiter = limiter(0.0, 0.0, 0.0);
+        limiter.config.enabled = false;
+
+        assert_eq!(allowed(&mut limiter, Token(1), Instant::now()), 100);
+    }
+
+}
@@ -1,13 +1,19 @@
 use serde::Deserialize;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
+use std::str::FromStr;
 use uuid::Uuid;
-use crate::team::Team as TeamColor;
+use codename_engine::team::Team as TeamColor;
+use codename_engine::action::Validate;
+pub use codename_engine::action::{Start, Hint, Guess};
+use crate::chat::{self, Channel};
 use crate::error::RequestError;
 
 #[derive(Deserialize, Debug)]
 pub struct Room {
     pub name: String,
     pub language: String,
+    pub password: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -17,8 +23,10 @@ pub struct Reset {
 
 #[derive(Deserialize, Debug)]
 pub struct Join {
-    pub id: Uuid,
+    pub id: Option<Uuid>,
+    pub code: Option<String>,
     pub name: String,
+    pub password: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -27,25 +35,19 @@ pub struct Team {
 }
 
 #[derive(Deserialize, Debug)]
-pub struct Start {
-    pub blue: String,
-    pub red: String
-}
-
-#[derive(Deserialize, Debug)]
-pub struct Hint {
-    pub hint: String,
-    pub guesses: u8,
+pub struct Pass {
 }
 
 #[derive(Deserialize, Debug)]
-pub struct Guess {
-    pub x: usize,
-    pub y: usize
+pub struct Password {
+    pub password: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
-pub struct Pass {
+pub struct Chat {
+    #[serde(default)]
+    pub channel: Channel,
+    pub message: String,
 }
 
 #[derive(Debug)]
@@ -58,11 +60,33 @@ pub enum Request {
     Guess(Guess),
     Pass(Pass),
     Reset(Reset),
+    Password(Password),
+    Chat(Chat),
 }
 
 impl Request {
 
-    pub fn from_str(text: &str) -> Result<Request, RequestError> {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Request::Room(_) => "room",
+            Request::Join(_) => "join",
+            Request::Team(_) => "team",
+            Request::Start(_) => "start",
+            Request::Hint(_) => "hint",
+            Request::Guess(_) => "guess",
+            Request::Pass(_) => "pass",
+            Request::Reset(_) => "reset",
+            Request::Password(_) => "password",
+            Request::Chat(_) => "chat",
+        }
+    }
+
+}
+
+impl FromStr for Request {
+    type Err = RequestError;
+
+    fn from_str(text: &str) -> Result<Request, RequestError> {
         log::debug!("request parse: {}", text);
         let data: Value = serde_json::from_str(text)?;
 
@@ -74,10 +98,12 @@ impl Request {
                 "room" => Room::parse(data),
                 "join" => Join::parse(data),
                 "team" => Team::parse(data),
-                "start" => Start::parse(data),
-                "hint" => Hint::parse(data),
-                "guess" => Guess::parse(data),
+                "start" => Ok(Request::Start(action(data)?)),
+                "hint" => Ok(Request::Hint(action(data)?)),
+                "guess" => Ok(Request::Guess(action(data)?)),
                 "pass" => Pass::parse(data),
+                "password" => Password::parse(data),
+                "chat" => Chat::parse(data),
                 _ => Err(RequestError::Unknown(request.clone()))
             }
         } else {
@@ -108,6 +134,11 @@ impl Join {
 
     pub fn parse(data: Value) -> Result<Request, RequestError> {
         let join: Join = serde_json::from_value(data)?;
+
+        if join.id.is_none() && join.code.is_none() {
+            return Err(RequestError::Missing("id"));
+        }
+
         Ok(Request::Join(join))
     }
 
@@ -123,70 +154,42 @@ impl Team {
 
 }
 
-impl Start {
+impl Pass {
 
     pub fn parse(data: Value) -> Result<Request, RequestError> {
-        let start: Start = serde_json::from_value(data)?;
-
-        if start.blue == "" {
-            return Err(RequestError::Missing("blue"));
-        }
-        if start.red == "" {
-            return Err(RequestError::Missing("red"));
-        }
-
-        Ok(Request::Start(start))
-    }
-
-    pub fn master(&self, team: &TeamColor) -> &str {
-        match team {
-            TeamColor::Red => &self.red,
-            TeamColor::Blue => &self.blue,
-        }
+        Ok(Request::Pass(serde_json::from_value(data)?))
     }
 
 }
 
-impl Hint {
+impl Password {
 
     pub fn parse(data: Value) -> Result<Request, RequestError> {
-        let hint: Hint = serde_json::from_value(data)?;
-
-        if hint.hint == "" {
-            return Err(RequestError::Missing("hint"));
-        }
-
-        if !(hint.guesses >= 1 && hint.guesses <= 9) {
-            return Err(RequestError::Invalid("guesses must be between 1 and 9"));
-        }
-
-        Ok(Request::Hint(hint))
+        Ok(Request::Password(serde_json::from_value(data)?))
     }
 
 }
 
-impl Guess {
+impl Chat {
 
     pub fn parse(data: Value) -> Result<Request, RequestError> {
-        let guess: Guess = serde_json::from_value(data)?;
+        let chat: Chat = serde_json::from_value(data)?;
 
-        if guess.x > 4 {
-            return Err(RequestError::Invalid("x must be between 0 and 4"));
+        if chat.message.trim().is_empty() {
+            return Err(RequestError::Missing("message"));
         }
 
-        if guess.y > 4 {
-            return Err(RequestError::Invalid("y must be between 0 and 4"));
+        if chat.message.chars().count() > chat::MAX_LENGTH {
+            return Err(RequestError::Invalid("message is too long"));
         }
 
-        Ok(Request::Guess(guess))
+        Ok(Request::Chat(chat))
     }
 
 }
 
-impl Pass {
-
-    pub fn parse(data: Value) -> Result<Request, RequestError> {
-        Ok(Request::Pass(serde_json::from_value(data)?))
-    }
-
+fn action<T: DeserializeOwned + Validate>(data: Value) -> Result<T, RequestError> {
+    let action: T = serde_json::from_value(data)?;
+    action.validate()?;
+    Ok(action)
 }
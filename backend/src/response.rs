@@ -1,31 +1,102 @@
-use serde_json::Value;
-use serde_json::json;
-use crate::game::Game;
-use crate::room::Room;
-use tungstenite::Message;
+use serde::Serialize;
+use codename_engine::board::TileMap;
+use codename_engine::game::Game;
+use codename_engine::team::Team;
+use crate::room::{self, Room};
+use crate::chat;
+use mio::Token;
 
 
-fn convert(data: Value) -> Message {
-    Message::Text(data.to_string())
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "response", rename_all = "lowercase")]
+pub enum Response {
+    Error {
+        error: String,
+    },
+    Room {
+        room: Box<room::View>,
+    },
+    Tiles {
+        tiles: TileMap,
+    },
+    Chat {
+        messages: Vec<chat::Message>,
+    },
+    Shutdown {
+        eta: Option<u64>,
+    },
 }
 
-pub fn error(msg: &str) -> Message {
-    convert(json!({
-        "response": "error",
-        "error": msg,
-    }))
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Audience {
+    All,
+    Team(Team),
+    Spymasters,
+    Player(Token),
 }
 
-pub fn room(room: &Room) -> Message {
-    convert(json!({
-        "response": "room",
-        "room": room,
-    }))
+// a response as emitted by a room, before its audience is resolved
+#[derive(Debug)]
+pub struct Outbound {
+    pub audience: Audience,
+    pub response: Response,
 }
 
-pub fn tiles(game: &Game) -> Message {
-    convert(json!({
-        "response": "tiles",
-        "tiles": game.board.tiles
-    }))
+// a response addressed to connections, ready to be encoded by the transport
+#[derive(Debug)]
+pub struct Envelope {
+    pub recipients: Vec<Token>,
+    pub response: Response,
+}
+
+impl Outbound {
+
+    pub fn new(audience: Audience, response: Response) -> Outbound {
+        Outbound {
+            audience,
+            response,
+        }
+    }
+
+}
+
+impl Envelope {
+
+    pub fn to(token: Token, response: Response) -> Envelope {
+        Envelope {
+            recipients: vec![token],
+            response,
+        }
+    }
+
+}
+
+pub fn error(msg: &str) -> Response {
+    Response::Error {
+        error: msg.to_string(),
+    }
+}
+
+pub fn room(room: &Room) -> Response {
+    Response::Room {
+        room: Box::new(room.view()),
+    }
+}
+
+pub fn tiles(game: &Game) -> Response {
+    Response::Tiles {
+        tiles: game.board.tiles.clone(),
+    }
+}
+
+pub fn chat(messages: &[&chat::Message]) -> Response {
+    Response::Chat {
+        messages: messages.iter().map(|m| (*m).clone()).collect(),
+    }
+}
+
+pub fn shutdown(eta: Option<u64>) -> Response {
+    Response::Shutdown {
+        eta,
+    }
 }
@@ -1,54 +1,63 @@
-use serde::ser::{Serialize, Serializer, SerializeStruct};
+use serde::Serialize;
 use crate::request;
-use crate::response;
-use crate::game::{Game, State};
-use crate::error::{RoomError, GameError};
-use crate::board::BoardSet;
+use crate::response::{self, Audience, Envelope, Outbound, Response};
+use codename_engine::game::{Game, State};
+use codename_engine::gameteam;
+use codename_engine::action;
+use codename_engine::spymaster::{Clues, Spymaster};
+use codename_engine::team::Team;
+use crate::error::RoomError;
+use codename_engine::error::GameError;
+use codename_engine::player::PlayerId;
+use codename_engine::board::{BoardSet, TileMap, WordMap};
+use crate::password::PasswordHash;
+use crate::chat::{self, Channel};
 use mio::Token;
 use uuid::Uuid;
-use tungstenite::Message;
-use std::rc::Rc;
-use std::collections::HashMap;
+use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
 use std::result;
 
-pub type Responses = Vec<(Token, Message)>;
+pub type Responses = Vec<Outbound>;
 type Result<T> = result::Result<T, RoomError>;
 
+const BOT_NAME: &str = "spymaster bot";
+
 #[derive(Debug)]
 pub struct Room {
     pub id: Uuid,
+    pub code: String,
     pub game: Game,
     pub players: HashMap<Token, String>,
-    boards: Rc<BoardSet>,
+    boards: Arc<BoardSet>,
+    spymaster: Option<Arc<Spymaster>>,
+    clues: Option<Arc<Clues>>,
+    password: Option<PasswordHash>,
+    chat: VecDeque<chat::Message>,
     admin: Token
 }
 
-impl Serialize for Room {
-    fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let players: Vec<&String> = self.players.values().collect();
-
-        let state = match self.game.state {
-            State::Start if self.players.len() >= 4 => "team",
-            State::Start => "join",
-            State::Play(_) => "play",
-            State::End(_) => "end"
-        };
+// snapshot of a room as sent to its players
+#[derive(Debug, Clone, Serialize)]
+pub struct View {
+    pub id: Uuid,
+    pub code: String,
+    pub game: Game,
+    pub players: Vec<String>,
+    pub state: &'static str,
+    pub private: bool,
+}
 
-        let mut s = serializer.serialize_struct("Room", 4)?;
-        s.serialize_field("id", &self.id)?;
-        s.serialize_field("game", &self.game)?;
-        s.serialize_field("players", &players)?;
-        s.serialize_field("state", state)?;
-        s.end()
-    }
+// a room with the key of its board, as written on shutdown
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    pub room: View,
+    pub tiles: TileMap,
 }
 
 impl Room {
 
-    pub fn new(boards: Rc<BoardSet>, admin: Token, request: &request::Room) -> Result<Room> {
+    pub fn new(boards: Arc<BoardSet>, spymaster: Option<Arc<Spymaster>>, code: String, admin: Token, request: &request::Room) -> Result<Room> {
         let board = boards.new_board(&request.language)?;
 
         let mut players = HashMap::new();
@@ -56,33 +65,73 @@ impl Room {
 
         Ok(Room {
             id: Uuid::new_v4(),
-            game: Game::new(board, admin),
-            boards: boards,
-            players: players,
-            admin: admin,
+            code,
+            game: Game::new(board, player_id(admin)),
+            boards,
+            spymaster,
+            clues: None,
+            password: hash_password(&request.password),
+            chat: VecDeque::new(),
+            players,
+            admin,
         })
     }
 
+    pub fn view(&self) -> View {
+        View {
+            id: self.id,
+            code: self.code.clone(),
+            game: self.game.clone(),
+            players: self.players.values().cloned().collect(),
+            state: self.state(),
+            private: self.password.is_some(),
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            room: self.view(),
+            tiles: self.game.board.tiles.clone(),
+        }
+    }
+
+    pub fn state(&self) -> &'static str {
+        match self.game.state {
+            State::Start if self.players.len() >= 4 => "team",
+            State::Start => "join",
+            State::Play(_) => "play",
+            State::End(_) => "end"
+        }
+    }
+
+    pub fn check_password(&self, password: &Option<String>) -> Result<()> {
+        match (&self.password, password) {
+            (None, _) => Ok(()),
+            (Some(hash), Some(password)) if hash.verify(password) => Ok(()),
+            _ => Err(RoomError::Password)
+        }
+    }
+
     pub fn is_alive(&self, token: Token) -> bool {
-        token != self.admin && self.players.len() > 0
+        token != self.admin && !self.players.is_empty()
     }
 
     pub fn remove_player(&mut self, token: Token) -> Responses {
         if let Some(name) = self.players.remove(&token) {
             log::info!("{} - removing player {}", self.id, name);
-            self.game.remove_player(token);
-            self.broadcast(response::room(&self))
+            self.game.remove_player(player_id(token));
+            self.broadcast(response::room(self))
         } else {
             Vec::new()
         }
     }
 
     pub fn broadcast_room(&self) -> Responses {
-        vec![(self.admin, response::room(self))]
+        vec![Outbound::new(Audience::Player(self.admin), response::room(self))]
     }
 
     pub fn handle(&mut self, token: Token, request: &request::Request) -> Result<Responses> {
-        match request {
+        let mut responses = match request {
             request::Request::Join(j) => self.join(token, j),
             request::Request::Team(t) => self.team(token, t),
             request::Request::Start(s) => self.start(token, s),
@@ -90,17 +139,46 @@ impl Room {
             request::Request::Guess(g) => self.guess(token, g),
             request::Request::Pass(_) => self.pass(token),
             request::Request::Reset(r) => self.reset(token, r),
+            request::Request::Password(p) => self.password(token, p),
+            request::Request::Chat(c) => self.chat(token, c),
             _ => {
                 Err(RoomError::Unhandled)
             }
+        }?;
+
+        responses.extend(self.play_bots());
+        Ok(responses)
+    }
+
+    pub fn broadcast(&self, response: Response) -> Responses {
+        vec![Outbound::new(Audience::All, response)]
+    }
+
+    pub fn recipients(&self, audience: Audience) -> Vec<Token> {
+        match audience {
+            Audience::All => self.players.keys().copied().collect(),
+            Audience::Team(team) => self.connected(self.game.team(&team).players.keys().copied()),
+            Audience::Spymasters => self.connected(self.game.masters()),
+            Audience::Player(token) => vec![token],
         }
     }
 
-    fn broadcast(&mut self, response: Message) -> Responses {
-        self.players.keys()
-            .map(|t| {
-                log::debug!("broadcasting to {}", t.0);
-                (*t, response.clone())
+    // bots have a seat in the game but no connection
+    fn connected(&self, players: impl Iterator<Item = PlayerId>) -> Vec<Token> {
+        players.map(player_token)
+            .filter(|token| self.players.contains_key(token))
+            .collect()
+    }
+
+    pub fn resolve(&self, responses: Responses) -> Vec<Envelope> {
+        responses.into_iter()
+            .map(|outbound| {
+                let recipients = self.recipients(outbound.audience);
+                log::debug!("{} - sending to {:?}", self.id, recipients);
+                Envelope {
+                    recipients,
+                    response: outbound.response,
+                }
             })
             .collect()
     }
@@ -110,70 +188,261 @@ impl Room {
 
         if token == self.admin {
             let board = self.boards.new_board(&reset.language)?;
-            self.game = Game::new(board, self.admin);
-            let response = response::room(&self);
+            self.game = Game::new(board, player_id(self.admin));
+            self.chat.retain(|m| m.channel == Channel::All);
+            let response = response::room(self);
             Ok(self.broadcast(response))
         } else {
             Err(GameError::NotAdmin)?
         }
     }
 
+    fn password(&mut self, token: Token, password: &request::Password) -> Result<Responses> {
+        if token != self.admin {
+            return Err(GameError::NotAdmin.into())
+        }
+
+        log::info!("{} - password changed", self.id);
+        self.password = hash_password(&password.password);
+
+        let response = response::room(self);
+        Ok(self.broadcast(response))
+    }
+
     fn join(&mut self, token: Token, join: &request::Join) -> Result<Responses> {
         log::info!("{} - {} has joined", self.id, join.name);
         self.players.insert(token, join.name.clone());
 
-        let response = response::room(&self);
-        Ok(self.broadcast(response))
+        let response = response::room(self);
+        let mut responses = self.broadcast(response);
+
+        let history: Vec<&chat::Message> = self.chat.iter()
+            .filter(|m| self.can_read(token, m))
+            .collect();
+
+        if !history.is_empty() {
+            responses.push(Outbound::new(Audience::Player(token), response::chat(&history)));
+        }
+
+        Ok(responses)
+    }
+
+    fn chat(&mut self, token: Token, chat: &request::Chat) -> Result<Responses> {
+        let name = self.players.get(&token).ok_or(GameError::NotFound("player"))?;
+        let team = self.game.player_team(player_id(token));
+
+        let audience = match chat.channel {
+            Channel::All => Audience::All,
+            Channel::Team => Audience::Team(team.ok_or(GameError::NotFound("team"))?),
+            Channel::Master => {
+                if !self.game.is_master(player_id(token)) {
+                    return Err(GameError::NotMaster.into())
+                }
+                Audience::Spymasters
+            }
+        };
+
+        let message = chat::Message {
+            channel: chat.channel,
+            name: name.clone(),
+            team,
+            message: chat.message.clone(),
+        };
+        log::debug!("{} - chat {:?}", self.id, message);
+
+        let response = response::chat(&[&message]);
+
+        if self.chat.len() >= chat::HISTORY {
+            self.chat.pop_front();
+        }
+        self.chat.push_back(message);
+
+        Ok(vec![Outbound::new(audience, response)])
+    }
+
+    fn can_read(&self, token: Token, message: &chat::Message) -> bool {
+        match message.channel {
+            Channel::All => true,
+            Channel::Team => message.team.is_some() && self.game.player_team(player_id(token)) == message.team,
+            Channel::Master => self.game.is_master(player_id(token)),
+        }
     }
 
     fn team(&mut self, token: Token, team: &request::Team) -> Result<Responses> {
         let name = self.players.get(&token).ok_or(GameError::NotFound("player"))?;
 
         log::info!("{} - player {:?} joined team {:?}", self.id, name, team.team);
-        self.game.add_player(token, team.team, &name);
+        self.game.add_player(player_id(token), team.team, name);
 
-        let response = response::room(&self);
+        let response = response::room(self);
         Ok(self.broadcast(response))
     }
 
     fn start(&mut self, token: Token, start: &request::Start) -> Result<Responses> {
-        self.game.start(token, start)?;
+        if token != self.admin {
+            return Err(GameError::NotAdmin.into())
+        }
+
+        let seated = match self.game.state {
+            State::Start => self.seat_bots(&start.bots)?,
+            _ => false
+        };
+
+        let masters = action::Start {
+            red: self.master_name(start, Team::Red),
+            blue: self.master_name(start, Team::Blue),
+            bots: Vec::new(),
+        };
+        if let Err(error) = self.game.start(player_id(token), &masters) {
+            if seated {
+                self.seat_bots(&[])?;
+            }
+            return Err(error.into())
+        }
+
+        // a player could share the name of the bot
+        for team in start.bots.iter() {
+            self.game.team_mut(team).master = Some(bot_id(*team));
+        }
         log::info!("{} - game started", self.id);
 
-        let mut responses = self.broadcast(response::room(&self));
+        let mut responses = self.broadcast(response::room(self));
 
-        let response = response::tiles(&self.game);
+        responses.push(Outbound::new(Audience::Spymasters, response::tiles(&self.game)));
+
+        Ok(responses)
+    }
 
-        if let Some(master) = self.game.red.master {
-            responses.push((master, response.clone()))
+    // adds the requested bots to their team and removes the others, true if any was seated
+    fn seat_bots(&mut self, bots: &[Team]) -> Result<bool> {
+        if !bots.is_empty() {
+            let spymaster = self.spymaster.as_ref().ok_or(RoomError::Bot("no word embeddings loaded"))?;
+            for team in bots {
+                if !spymaster.knows(&self.game.board, *team) {
+                    return Err(RoomError::Bot("the words of this board are not in the embeddings"))
+                }
+            }
         }
 
-        if let Some(master) = self.game.blue.master {
-            responses.push((master, response.clone()))
+        for team in [Team::Red, Team::Blue].iter() {
+            if bots.contains(team) {
+                self.game.add_player(bot_id(*team), *team, BOT_NAME);
+            } else {
+                self.game.remove_player(bot_id(*team));
+            }
         }
 
-        Ok(responses)
+        Ok(!bots.is_empty())
+    }
+
+    fn master_name(&self, start: &request::Start, team: Team) -> String {
+        if start.bots.contains(&team) {
+            BOT_NAME.to_string()
+        } else {
+            start.master(&team).to_string()
+        }
+    }
+
+    // the board to compute clues for, when a bot is seated and the clues are missing
+    pub fn wants_clues(&self) -> Option<(Arc<Spymaster>, WordMap)> {
+        let spymaster = self.spymaster.as_ref()?;
+        let seated = [Team::Red, Team::Blue].iter()
+            .any(|team| self.game.team(team).master == Some(bot_id(*team)));
+        let cached = self.clues.as_ref()
+            .is_some_and(|clues| clues.matches(&self.game.board.words));
+
+        if seated && !cached {
+            Some((spymaster.clone(), self.game.board.words.clone()))
+        } else {
+            None
+        }
+    }
+
+    pub fn set_clues(&mut self, clues: Arc<Clues>) -> Responses {
+        // the board may have been reset in the meantime
+        if clues.matches(&self.game.board.words) {
+            self.clues = Some(clues);
+        }
+        self.play_bots()
+    }
+
+    // bot spymasters give their hint through the same path as players, as soon as their team is up
+    // and the clues of the board are computed
+    fn play_bots(&mut self) -> Responses {
+        let mut responses = Vec::new();
+
+        while let State::Play(team) = self.game.state {
+            let current = self.game.team(&team);
+            if current.master != Some(bot_id(team)) || !matches!(current.state, gameteam::State::Hint) {
+                break
+            }
+
+            let (spymaster, clues) = match (&self.spymaster, &self.clues) {
+                (Some(spymaster), Some(clues)) if clues.matches(&self.game.board.words) => (spymaster, clues),
+                _ => break
+            };
+
+            let hint = match spymaster.hint(clues, &self.game.board, team) {
+                Some(hint) => hint,
+                None => {
+                    log::error!("{} - spymaster bot has no hint for {:?}", self.id, team);
+                    break
+                }
+            };
+
+            match self.hint(player_token(bot_id(team)), &hint) {
+                Ok(hinted) => responses.extend(hinted),
+                Err(error) => {
+                    log::error!("{} - spymaster bot hint refused: {}", self.id, error);
+                    break
+                }
+            }
+        }
+
+        responses
     }
 
     fn hint(&mut self, token: Token, hint: &request::Hint) -> Result<Responses> {
-        self.game.hint(token, &hint)?;
+        self.game.hint(player_id(token), hint)?;
         log::info!("{} - hint {:?}", self.id, hint);
 
-        Ok(self.broadcast(response::room(&self)))
+        Ok(self.broadcast(response::room(self)))
     }
 
     fn guess(&mut self, token: Token, guess: &request::Guess) -> Result<Responses> {
-        self.game.guess(token, &guess)?;
+        self.game.guess(player_id(token), guess)?;
         log::info!("{} - guess {} {}", self.id, guess.x, guess.y);
 
-        Ok(self.broadcast(response::room(&self)))
+        Ok(self.broadcast(response::room(self)))
     }
 
     fn pass(&mut self, token: Token) -> Result<Responses> {
-        self.game.pass(token)?;
+        self.game.pass(player_id(token))?;
         log::info!("{} - pass", self.id);
 
-        Ok(self.broadcast(response::room(&self)))
+        Ok(self.broadcast(response::room(self)))
     }
 
 }
+
+pub fn player_id(token: Token) -> PlayerId {
+    PlayerId(token.0)
+}
+
+pub fn player_token(player: PlayerId) -> Token {
+    Token(player.0)
+}
+
+// bots have no connection, their ids stay clear of the tokens used by the transports
+pub fn bot_id(team: Team) -> PlayerId {
+    match team {
+        Team::Red => PlayerId(usize::MAX - 16),
+        Team::Blue => PlayerId(usize::MAX - 17),
+    }
+}
+
+fn hash_password(password: &Option<String>) -> Option<PasswordHash> {
+    password.as_ref()
+        .filter(|p| !p.is_empty())
+        .map(|p| PasswordHash::new(p))
+}
@@ -1,107 +1,349 @@
-use crate::board::BoardSet;
-use crate::room::Room;
+use codename_engine::board::BoardSet;
+use codename_engine::spymaster::Spymaster;
+use crate::room::{Room, Snapshot};
 use crate::request;
-use crate::response;
-use crate::error::{GameError, RoomError};
-use crate::stream::{Stream, EventKind};
+use crate::response::{self, Envelope};
+use crate::error::{RoomError, RequestError};
+use codename_engine::error::GameError;
+use crate::transport::{Transport, EventKind};
+use crate::codegenerator::{self, CodeGenerator};
+use crate::metrics::Metrics;
+use crate::ratelimit::{self, RateLimiter, Verdict};
+use crate::worker::{Worker, Command, Reply};
 use uuid::Uuid;
 use mio::Token;
-use std::collections::HashMap;
+use tungstenite::protocol::frame::coding::CloseCode;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::error::Error;
-use std::rc::Rc;
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
 
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+const ROOMS_WINDOW: Duration = Duration::from_secs(3600);
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
 
-pub struct Server {
-    stream: Stream,
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub max_rooms: Option<usize>,
+    pub max_players: Option<usize>,
+    pub max_rooms_per_ip: Option<usize>,
+    pub restart_eta: Option<u64>,
+    pub rate_limit: ratelimit::Config,
+    pub workers: usize,
+    pub spymaster: Option<Arc<Spymaster>>,
+    // file the rooms are written to on shutdown
+    pub snapshot: Option<PathBuf>,
+}
+
+struct RoomInfo {
+    worker: usize,
+    code: String,
+    state: &'static str,
+}
+
+pub struct Server<T> {
+    config: Config,
+    stream: T,
     players: HashMap<Token, Uuid>,
-    rooms: HashMap<Uuid, Room>,
-    boardset: Rc<BoardSet>
+    // workers each token was routed to, and acknowledgements awaited before it is released
+    routed: HashMap<Token, Vec<usize>>,
+    releasing: HashMap<Token, usize>,
+    // rooms created from each address within the last ROOMS_WINDOW
+    created: HashMap<IpAddr, VecDeque<Instant>>,
+    swept: Instant,
+    rooms: HashMap<Uuid, RoomInfo>,
+    codes: HashMap<String, Uuid>,
+    generator: CodeGenerator,
+    metrics: Metrics,
+    limiter: RateLimiter,
+    workers: Vec<Worker>,
+    snapshots: Vec<Snapshot>,
+    replies: Receiver<Reply>,
+    boardset: Arc<BoardSet>
 }
 
 
-impl Server {
+impl<T: Transport> Server<T> {
+
+    pub fn new(boardset: Arc<BoardSet>, stream: T, config: Config) -> io::Result<Server<T>> {
+        let (sender, replies) = mpsc::channel();
+        let waker = stream.waker()?;
 
-    pub fn new(boardset: Rc<BoardSet>, stream: Stream) -> Server {
-        Server {
-            boardset: boardset,
-            stream: stream,
+        let workers = (0..config.workers.max(1))
+            .map(|i| Worker::spawn(i, config.max_players, sender.clone(), waker.clone()))
+            .collect::<io::Result<Vec<Worker>>>()?;
+        log::info!("started {} room workers", workers.len());
+
+        Ok(Server {
+            limiter: RateLimiter::new(config.rate_limit.clone()),
+            workers,
+            snapshots: Vec::new(),
+            replies,
+            config,
+            boardset,
+            stream,
             players: HashMap::new(),
+            routed: HashMap::new(),
+            releasing: HashMap::new(),
+            created: HashMap::new(),
+            swept: Instant::now(),
             rooms: HashMap::new(),
-        }
+            codes: HashMap::new(),
+            generator: CodeGenerator::new(),
+            metrics: Metrics::new(),
+        })
     }
 
     pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
         loop {
-            for event in self.stream.poll()? {
+            let events = self.stream.poll()?;
+            let start = Instant::now();
+
+            for event in events {
                 log::debug!("handling event: {:?}", event);
                 match event.kind {
                     EventKind::Request(request) => {
-                        if let Err(error) = self.handle_request(event.token, request) {
-                            self.stream.push(event.token, response::error(&error.to_string()))
+                        self.metrics.request(request.kind());
+                        if self.allow(event.token, request.kind()) {
+                            if let Err(error) = self.handle_request(event.token, request) {
+                                self.metrics.error(error.variant());
+                                self.stream.send(Envelope::to(event.token, response::error(&error.to_string())))
+                            }
                         }
                     }
                     EventKind::Error(error) => {
-                        self.stream.push(event.token, response::error(&error.to_string()))
+                        // invalid requests are limited too, under their own kind
+                        if self.allow(event.token, "parse") {
+                            self.metrics.error(error.variant());
+                            self.stream.send(Envelope::to(event.token, response::error(&error.to_string())))
+                        }
+                    },
+                    EventKind::Http(path) => {
+                        self.handle_http(event.token, &path)
                     },
                     EventKind::Close => {
-                        self.remove_player(event.token)
+                        self.remove_player(event.token);
+                        self.limiter.remove(event.token);
+                        self.stream.remove(event.token);
+                        self.retire(event.token);
+                    },
+                    EventKind::Shutdown => {
+                        return self.shutdown()
                     }
                 }
             }
+
+            self.handle_replies();
+            if self.swept.elapsed() >= SWEEP_INTERVAL {
+                self.sweep_created();
+            }
+            self.metrics.observe(start.elapsed());
         }
     }
 
-    fn remove_player(&mut self, token: Token) {
-        if let Some(id) = self.players.remove(&token) {
+    fn allow(&mut self, token: Token, kind: &'static str) -> bool {
+        match self.limiter.check(token, kind) {
+            Verdict::Allow => true,
+            Verdict::Throttle => {
+                let error = RequestError::Throttled(kind);
+                self.metrics.error(error.variant());
+                self.stream.send(Envelope::to(token, response::error(&error.to_string())));
+                false
+            },
+            Verdict::Disconnect => {
+                log::info!("disconnecting {} for exceeding rate limits", token.0);
+                self.remove_player(token);
+                self.stream.close(token, CloseCode::Policy, "rate limit exceeded");
+                false
+            }
+        }
+    }
+
+    fn handle_replies(&mut self) {
+        while let Ok(reply) = self.replies.try_recv() {
+            match reply {
+                Reply::Responses(envelopes) => {
+                    self.stream.send_all(envelopes)
+                },
+                Reply::Error(token, error) => {
+                    self.metrics.error(error.variant());
+                    self.stream.send(Envelope::to(token, response::error(&error.to_string())))
+                },
+                Reply::Rejected(id, token, error) => {
+                    if self.players.get(&token) == Some(&id) {
+                        self.players.remove(&token);
+                    }
+                    self.metrics.error(error.variant());
+                    self.stream.send(Envelope::to(token, response::error(&error.to_string())))
+                },
+                Reply::State(id, state) => {
+                    if let Some(room) = self.rooms.get_mut(&id) {
+                        if room.state != "end" && state == "end" {
+                            self.metrics.games_finished += 1;
+                        }
+                        room.state = state;
+                    }
+                },
+                Reply::Started => {
+                    self.metrics.games_started += 1;
+                },
+                Reply::Snapshot(snapshots) => {
+                    self.snapshots.extend(snapshots)
+                },
+                Reply::Closed(id, tokens) => {
+                    self.remove_room(id, tokens)
+                },
+                Reply::Released(token) => {
+                    if let Some(count) = self.releasing.get_mut(&token) {
+                        *count -= 1;
+                        if *count == 0 {
+                            self.releasing.remove(&token);
+                            self.stream.release(token);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_http(&mut self, token: Token, path: &str) {
+        match path {
+            "/health" => {
+                self.stream.respond(token, 200, "OK", "text/plain", "ok\n")
+            },
+            "/metrics" => {
+                let mut rooms = BTreeMap::new();
+                for room in self.rooms.values() {
+                    *rooms.entry(room.state).or_insert(0) += 1;
+                }
 
-            let mut remove = false;
-            if let Some(room) = self.rooms.get_mut(&id) {
-                self.stream.append(room.remove_player(token));
-                remove = !room.is_alive(token) 
+                let body = self.metrics.render(self.stream.connections(), &rooms);
+                self.stream.respond(token, 200, "OK", "text/plain; version=0.0.4", &body)
+            },
+            _ => {
+                self.stream.respond(token, 404, "Not Found", "text/plain", "not found\n")
             }
+        }
+    }
+
+    fn shutdown(&mut self) -> Result<(), Box<dyn Error>> {
+        log::info!("shutting down, notifying {} rooms", self.rooms.len());
+
+        let response = response::shutdown(self.config.restart_eta);
+        let workers = mem::take(&mut self.workers);
+        for worker in workers.iter() {
+            worker.send(Command::Shutdown(response.clone()));
+        }
+        for worker in workers {
+            worker.join();
+        }
+        self.handle_replies();
 
-            if remove {
-                self.remove_room(id);
+        if let Some(path) = &self.config.snapshot {
+            match self.write_snapshot(path) {
+                Ok(()) => log::info!("wrote {} rooms to {}", self.snapshots.len(), path.display()),
+                Err(e) => log::error!("could not write snapshot to {}: {}", path.display(), e)
             }
-        } 
+        }
+
+        self.stream.shutdown(SHUTDOWN_TIMEOUT)?;
+        Ok(())
     }
-    
-    fn remove_room(&mut self, id: Uuid) {
+
+    fn write_snapshot(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = File::create(path)?;
+        serde_json::to_writer(file, &self.snapshots)?;
+        Ok(())
+    }
+
+    fn remove_player(&mut self, token: Token) {
+        if let Some(id) = self.players.remove(&token) {
+            if let Some(worker) = self.rooms.get(&id).map(|room| room.worker) {
+                self.dispatch(worker, token, Command::Leave(id, token));
+            }
+        }
+    }
+
+    fn dispatch(&mut self, worker: usize, token: Token, command: Command) {
+        let routed = self.routed.entry(token).or_default();
+        if !routed.contains(&worker) {
+            routed.push(worker);
+        }
+        self.workers[worker].send(command);
+    }
+
+    // a removed token is only reused once the workers it was routed to are done with it,
+    // otherwise their replies would reach the next connection given the token
+    fn retire(&mut self, token: Token) {
+        if self.releasing.contains_key(&token) {
+            return
+        }
+
+        match self.routed.remove(&token) {
+            Some(workers) => {
+                for worker in workers.iter() {
+                    self.workers[*worker].send(Command::Release(token));
+                }
+                self.releasing.insert(token, workers.len());
+            },
+            None => self.stream.release(token)
+        }
+    }
+
+    fn remove_room(&mut self, id: Uuid, tokens: Vec<Token>) {
         if let Some(room) = self.rooms.remove(&id) {
-            log::info!("{} - closing room", id);
-            for token in room.game.tokens() {
-                self.players.remove(token);
-                self.stream.remove(*token);
+            self.codes.remove(&room.code);
+            self.generator.recycle(&room.code);
+            if let Some(worker) = self.workers.get_mut(room.worker) {
+                worker.rooms -= 1;
+            }
+            // players who already left were retired on their own
+            for token in tokens {
+                if self.players.get(&token) != Some(&id) {
+                    continue
+                }
+                self.players.remove(&token);
+                self.limiter.remove(token);
+                self.stream.remove(token);
+                self.retire(token);
             }
         }
     }
-    
+
     fn handle_request(&mut self, token: Token, request: request::Request) -> Result<(), RoomError> {
         if self.players.contains_key(&token) {
-            self.handle_room(token, &request)
+            self.handle_room(token, request)
         } else {
-            self.handle_client(token, &request)
+            self.handle_client(token, request)
         }
     }
 
-    fn handle_room(&mut self, token: Token, request: &request::Request) -> Result<(), RoomError> {
-        let id = self.players.get(&token).ok_or(GameError::NotFound("player"))?;
-        let room = self.rooms.get_mut(id).ok_or(RoomError::NotFound(id.clone()))?;
+    fn handle_room(&mut self, token: Token, request: request::Request) -> Result<(), RoomError> {
+        let id = *self.players.get(&token).ok_or(GameError::NotFound("player"))?;
+        let worker = self.rooms.get(&id).ok_or(RoomError::NotFound(id))?.worker;
 
-        log::debug!("{} - handle token {} request {:?}", room.id, token.0, request);
-        let responses = room.handle(token, &request)?;
-        self.stream.append(responses);
-        
+        self.dispatch(worker, token, Command::Request(id, token, request));
         Ok(())
     }
 
-    fn handle_client(&mut self, token: Token, request: &request::Request) -> Result<(), RoomError> {
+    fn handle_client(&mut self, token: Token, request: request::Request) -> Result<(), RoomError> {
         match &request {
             request::Request::Room(r) => self.new_room(token, r),
-            request::Request::Join(j) if self.rooms.contains_key(&j.id) => {
-                log::debug!("{} - adding token {}", j.id, token.0);
-                self.players.insert(token, j.id);
-                self.handle_room(token, request)
+            request::Request::Join(j) => {
+                let id = self.find_room(j).ok_or(RoomError::Forbidden)?;
+                let worker = self.rooms[&id].worker;
+
+                // the worker owning the room rejects the join if needed
+                self.players.insert(token, id);
+                self.dispatch(worker, token, Command::Join(id, token, request));
+                Ok(())
             },
             _ => {
                 Err(RoomError::Forbidden)
@@ -109,13 +351,85 @@ impl Server {
         }
     }
 
+    fn find_room(&self, join: &request::Join) -> Option<Uuid> {
+        if let Some(id) = join.id {
+            if self.rooms.contains_key(&id) {
+                return Some(id)
+            }
+        }
+
+        join.code.as_ref()
+            .and_then(|code| self.codes.get(&codegenerator::normalize(code)))
+            .copied()
+    }
+
+    // forgets the addresses which created no room within the window
+    fn sweep_created(&mut self) {
+        let now = Instant::now();
+        self.created.retain(|_, created| {
+            expire(created, now);
+            !created.is_empty()
+        });
+        self.swept = now;
+    }
+
     fn new_room(&mut self, token: Token, request: &request::Room) -> Result<(), RoomError> {
-        let room = Room::new(self.boardset.clone(), token, request)?;
-        log::info!("{} - new room created by {}", room.id, request.name);
+        if let Some(max) = self.config.max_rooms {
+            if self.rooms.len() >= max {
+                return Err(RoomError::Limit("rooms"))
+            }
+        }
+
+        let address = self.stream.address(token);
+        if let (Some(max), Some(ip)) = (self.config.max_rooms_per_ip, address) {
+            if let Some(created) = self.created.get_mut(&ip) {
+                expire(created, Instant::now());
+                if created.is_empty() {
+                    self.created.remove(&ip);
+                } else if created.len() >= max {
+                    return Err(RoomError::Limit("rooms created from this address"))
+                }
+            }
+        }
+
+        let code = self.generator.next().ok_or(RoomError::Code)?;
+        let room = match Room::new(self.boardset.clone(), self.config.spymaster.clone(), code.clone(), token, request) {
+            Ok(room) => room,
+            Err(error) => {
+                self.generator.recycle(&code);
+                return Err(error)
+            }
+        };
+
+        let worker = (0..self.workers.len())
+            .min_by_key(|i| self.workers[*i].rooms)
+            .unwrap_or(0);
+
+        log::info!("{} - new room {} created by {} on worker {}", room.id, room.code, request.name, worker);
         self.players.insert(token, room.id);
-        self.stream.append(room.broadcast_room());
-        self.rooms.insert(room.id, room);
+        if let (Some(_), Some(ip)) = (self.config.max_rooms_per_ip, address) {
+            self.created.entry(ip).or_default().push_back(Instant::now());
+        }
+        self.codes.insert(code.clone(), room.id);
+        self.stream.send_all(room.resolve(room.broadcast_room()));
+        self.rooms.insert(room.id, RoomInfo {
+            worker,
+            code,
+            state: room.state(),
+        });
+
+        self.workers[worker].rooms += 1;
+        self.dispatch(worker, token, Command::Insert(Box::new(room)));
 
         Ok(())
     }
 }
+
+fn expire(created: &mut VecDeque<Instant>, now: Instant) {
+    while let Some(first) = created.front() {
+        if now.duration_since(*first) < ROOMS_WINDOW {
+            break
+        }
+        created.pop_front();
+    }
+}
@@ -0,0 +1,65 @@
+use serde::Deserialize;
+use mio::net::TcpStream;
+use native_tls::{Identity, TlsAcceptor, TlsStream};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tls {
+    pub certificate: PathBuf,
+    pub key: PathBuf,
+}
+
+pub enum Socket {
+    Plain(TcpStream),
+    Tls(TlsStream<TcpStream>),
+}
+
+impl Socket {
+
+    pub fn get_mut(&mut self) -> &mut TcpStream {
+        match self {
+            Socket::Plain(s) => s,
+            Socket::Tls(s) => s.get_mut(),
+        }
+    }
+
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Socket::Plain(s) => s.read(buf),
+            Socket::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Socket::Plain(s) => s.write(buf),
+            Socket::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Socket::Plain(s) => s.flush(),
+            Socket::Tls(s) => s.flush(),
+        }
+    }
+}
+
+pub fn acceptor(tls: &Tls) -> io::Result<TlsAcceptor> {
+    let certificate = fs::read(&tls.certificate)?;
+    let key = fs::read(&tls.key)?;
+
+    let identity = Identity::from_pkcs8(&certificate, &key)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    TlsAcceptor::new(identity)
+        .map_err(io::Error::other)
+}
@@ -1,48 +1,164 @@
 use crate::idgenerator::IdGenerator;
-use crate::request::Request;
-use crate::error::RequestError;
+use crate::upgrade::{Upgrade, Check};
+use crate::socket::{self, Socket, Tls};
+use crate::admin;
+use crate::line;
+use crate::transport::{self, Transport, Event, EventKind};
 use mio::net::{TcpListener, TcpStream};
-use mio::{Events, Interest, Poll, Token};
-use std::collections::HashMap;
-use tungstenite::{WebSocket, Message, accept};
+use mio::{Events, Interest, Poll, Token, Waker};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::mem;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tungstenite::{WebSocket, Message, HandshakeError};
+use tungstenite::handshake::MidHandshake;
+use tungstenite::handshake::server::ServerHandshake;
+use tungstenite::protocol::WebSocketConfig;
 use tungstenite::Error as WsError;
-use std::io;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook_mio::v0_7::Signals;
+use tungstenite::protocol::frame::CloseFrame;
+use tungstenite::protocol::frame::coding::CloseCode;
+use native_tls::{TlsAcceptor, TlsStream, MidHandshakeTlsStream, HandshakeError as TlsHandshakeError};
+use std::io::{self, Write};
 
 const LISTENER: Token = Token(0);
+const SIGNAL: Token = Token(usize::MAX);
+const ADMIN: Token = Token(usize::MAX - 1);
+const WAKER: Token = Token(usize::MAX - 2);
+const TCP: Token = Token(usize::MAX - 3);
+const TICK: Duration = Duration::from_secs(1);
+
+type Handshake = MidHandshake<ServerHandshake<Socket, Check>>;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub ping_interval: Duration,
+    pub idle_timeout: Duration,
+    pub handshake_timeout: Duration,
+    pub max_queue: usize,
+    pub max_message_size: usize,
+    pub max_connections: Option<usize>,
+    pub max_connections_per_ip: Option<usize>,
+    pub upgrade: Upgrade,
+    pub tls: Option<Tls>,
+    pub admin: Option<SocketAddr>,
+    pub tcp: Option<SocketAddr>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            ping_interval: Duration::from_secs(15),
+            idle_timeout: Duration::from_secs(45),
+            handshake_timeout: Duration::from_secs(10),
+            max_queue: 64,
+            max_message_size: 64 << 10,
+            max_connections: None,
+            max_connections_per_ip: None,
+            upgrade: Upgrade::default(),
+            tls: None,
+            admin: None,
+            tcp: None,
+        }
+    }
+}
+
+impl Config {
+
+    pub fn websocket(&self) -> WebSocketConfig {
+        WebSocketConfig {
+            max_send_queue: None,
+            max_message_size: Some(self.max_message_size),
+            max_frame_size: Some(self.max_message_size),
+            accept_unmasked_frames: false,
+        }
+    }
+
+}
+
+struct Activity {
+    seen: Instant,
+    ping: Instant,
+}
+
+impl Activity {
+    fn new() -> Activity {
+        let now = Instant::now();
+        Activity {
+            seen: now,
+            ping: now,
+        }
+    }
+}
 
 pub struct Stream {
-    sockets: HashMap<Token, TcpStream>,
-    ws: HashMap<Token, WebSocket<TcpStream>>,
+    tls: HashMap<Token, MidHandshakeTlsStream<TcpStream>>,
+    handshakes: HashMap<Token, Handshake>,
+    ws: HashMap<Token, WebSocket<Socket>>,
+    acceptor: Option<TlsAcceptor>,
+    activity: HashMap<Token, Activity>,
+    addresses: HashMap<Token, IpAddr>,
+    per_ip: HashMap<IpAddr, usize>,
+    refused: HashMap<Token, &'static str>,
+    closing: HashMap<Token, Instant>,
+    retired: HashSet<Token>,
+    config: Config,
     generator: IdGenerator,
     poll: Poll,
     listener: TcpListener,
+    admin: Option<TcpListener>,
+    http: HashMap<Token, admin::Connection>,
+    tcp: Option<TcpListener>,
+    lines: HashMap<Token, line::Connection>,
+    signals: Signals,
     events: Vec<Event>,
-    responses: Vec<(Token, Message)>
-}
-
-#[derive(Debug)]
-pub struct Event {
-    pub token: Token,
-    pub kind: EventKind,
-}
-
-#[derive(Debug)]
-pub enum EventKind {
-    Request( Request),
-    Error(RequestError),
-    Close
+    responses: HashMap<Token, VecDeque<Message>>,
+    pending: HashSet<Token>,
+    writable: HashSet<Token>,
+    // listeners to accept from again, after running out of file descriptors
+    backlog: HashSet<Token>,
 }
 
 impl Stream {
 
-    pub fn new(listener: TcpListener) -> io::Result<Stream> {
+    pub fn new(listener: TcpListener, config: Config) -> io::Result<Stream> {
+        let acceptor = match &config.tls {
+            Some(tls) => Some(socket::acceptor(tls)?),
+            None => None
+        };
+
         let mut stream = Stream {
-            listener: listener,
-            sockets: HashMap::new(),
+            listener,
+            tls: HashMap::new(),
+            handshakes: HashMap::new(),
             ws: HashMap::new(),
+            acceptor,
+            admin: match config.admin {
+                Some(addr) => Some(TcpListener::bind(addr)?),
+                None => None
+            },
+            http: HashMap::new(),
+            tcp: match config.tcp {
+                Some(addr) => Some(TcpListener::bind(addr)?),
+                None => None
+            },
+            lines: HashMap::new(),
+            activity: HashMap::new(),
+            addresses: HashMap::new(),
+            per_ip: HashMap::new(),
+            refused: HashMap::new(),
+            closing: HashMap::new(),
+            retired: HashSet::new(),
+            config,
             generator: IdGenerator::new(),
             poll: Poll::new()?,
-            responses: Vec::new(),
+            signals: Signals::new([SIGINT, SIGTERM])?,
+            responses: HashMap::new(),
+            pending: HashSet::new(),
+            writable: HashSet::new(),
+            backlog: HashSet::new(),
             events: Vec::new(),
         };
 
@@ -53,155 +169,841 @@ impl Stream {
     fn init(&mut self) -> io::Result<()> {
         self.poll.registry()
             .register(&mut self.listener, LISTENER, Interest::READABLE)?;
+        self.poll.registry()
+            .register(&mut self.signals, SIGNAL, Interest::READABLE)?;
+
+        if let Some(admin) = self.admin.as_mut() {
+            self.poll.registry()
+                .register(admin, ADMIN, Interest::READABLE)?;
+        }
+
+        if let Some(tcp) = self.tcp.as_mut() {
+            self.poll.registry()
+                .register(tcp, TCP, Interest::READABLE)?;
+        }
         Ok(())
     }
 
-    pub fn poll(&mut self) -> io::Result<Vec<Event>> {
-        self.reregister()?;
+    fn heartbeat(&mut self) {
+        let now = Instant::now();
+        let mut pings = Vec::new();
 
-        let mut events = Events::with_capacity(128);
+        for (token, since) in self.closing.iter() {
+            if now.duration_since(*since) > self.config.handshake_timeout {
+                log::info!("close timeout on {}", token.0);
+                self.events.push(Event{
+                    token: *token,
+                    kind: EventKind::Close
+                });
+            }
+        }
 
-        log::debug!("polling");
-        self.poll.poll(&mut events, None)?;
+        for (token, activity) in self.activity.iter_mut() {
+            let handshake = self.tls.contains_key(token)
+                || self.handshakes.contains_key(token)
+                || self.http.contains_key(token);
 
-        for event in events.iter() {
-            let token = event.token();
-            log::debug!("event: {:?}", event);
+            if handshake && now.duration_since(activity.seen) > self.config.handshake_timeout {
+                log::info!("handshake timeout on {}", token.0);
+                self.events.push(Event{
+                    token: *token,
+                    kind: EventKind::Close
+                });
+            } else if now.duration_since(activity.seen) > self.config.idle_timeout {
+                log::info!("closing idle connection {}", token.0);
+                self.events.push(Event{
+                    token: *token,
+                    kind: EventKind::Close
+                });
+            } else if self.ws.contains_key(token) && now.duration_since(activity.ping) >= self.config.ping_interval {
+                activity.ping = now;
+                pings.push((*token, Message::Ping(Vec::new())));
+            }
+        }
 
-            if token == LISTENER {
-                self.register()?;
-            } else if event.is_readable() {
-                self.read(token);
-            } else if event.is_writable() {
-                self.write(token)?;
+        for (token, ping) in pings {
+            self.push(token, ping);
+        }
+    }
+
+    fn accept(&mut self, listener: Token) {
+        match listener {
+            LISTENER => self.register(),
+            ADMIN => self.register_admin(),
+            TCP => self.register_tcp(),
+            _ => {}
+        }
+    }
+
+    // true when accepting can go on, otherwise the listener is tried again on the next poll
+    fn accept_error(&mut self, listener: Token, e: &io::Error) -> bool {
+        if aborted(e) {
+            return true
+        }
+
+        self.backlog.insert(listener);
+        false
+    }
+
+    fn register(&mut self) {
+        loop {
+            let (mut sock, addr) = match self.listener.accept() {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    log::error!("accept error: {}", e);
+                    if self.accept_error(LISTENER, &e) {
+                        continue
+                    }
+                    return
+                }
+            };
+            let token = Token(self.generator.next());
+            let ip = addr.ip();
+
+            if let Err(e) = self.poll.registry().register(&mut sock, token, Interest::READABLE | Interest::WRITABLE) {
+                log::error!("register error: {}", e);
+                self.generator.recycle(token.0);
+                continue
+            }
+
+            if let Some(reason) = self.refuse(ip) {
+                self.refused.insert(token, reason);
+            }
+
+            self.writable.insert(token);
+            self.activity.insert(token, Activity::new());
+            self.addresses.insert(token, ip);
+            *self.per_ip.entry(ip).or_insert(0) += 1;
+
+            match &self.acceptor {
+                Some(acceptor) => self.accept_tls(token, acceptor.accept(sock)),
+                None => self.start_handshake(token, Socket::Plain(sock)),
             }
         }
+    }
 
-        Ok(self.events.drain(..).collect())
+    fn refuse(&self, ip: IpAddr) -> Option<&'static str> {
+        if let Some(max) = self.config.max_connections {
+            if self.connections() >= max {
+                return Some("too many connections")
+            }
+        }
+
+        if let Some(max) = self.config.max_connections_per_ip {
+            if self.per_ip.get(&ip).copied().unwrap_or(0) >= max {
+                return Some("too many connections from this address")
+            }
+        }
+
+        None
     }
 
-    pub fn register(&mut self) -> io::Result<()> {
-        let (mut sock, _) = self.listener.accept()?;
-        let token = Token(self.generator.next());
+    fn register_admin(&mut self) {
+        loop {
+            let accepted = match self.admin.as_ref() {
+                Some(admin) => admin.accept(),
+                None => return
+            };
 
-        self.poll.registry().register(&mut sock, token, Interest::READABLE)?; 
-        self.sockets.insert(token, sock);
+            let (mut sock, _) = match accepted {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    log::error!("admin accept error: {}", e);
+                    if self.accept_error(ADMIN, &e) {
+                        continue
+                    }
+                    return
+                }
+            };
+            let token = Token(self.generator.next());
 
-        Ok(())
+            if let Err(e) = self.poll.registry().register(&mut sock, token, Interest::READABLE | Interest::WRITABLE) {
+                log::error!("admin register error: {}", e);
+                self.generator.recycle(token.0);
+                continue
+            }
+
+            self.http.insert(token, admin::Connection::new(sock));
+            self.activity.insert(token, Activity::new());
+        }
+    }
+
+    fn register_tcp(&mut self) {
+        loop {
+            let accepted = match self.tcp.as_ref() {
+                Some(tcp) => tcp.accept(),
+                None => return
+            };
+
+            let (mut sock, addr) = match accepted {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    log::error!("tcp accept error: {}", e);
+                    if self.accept_error(TCP, &e) {
+                        continue
+                    }
+                    return
+                }
+            };
+            let ip = addr.ip();
+
+            if let Some(reason) = self.refuse(ip) {
+                log::info!("refusing tcp connection from {}: {}", ip, reason);
+                // a fresh socket has room for one short line, it is not worth waiting for
+                match sock.write(&line::refused(reason)) {
+                    Err(e) if e.kind() != io::ErrorKind::WouldBlock => log::debug!("tcp refusal error: {}", e),
+                    _ => {}
+                }
+                continue
+            }
+
+            let token = Token(self.generator.next());
+            if let Err(e) = self.poll.registry().register(&mut sock, token, Interest::READABLE | Interest::WRITABLE) {
+                log::error!("tcp register error: {}", e);
+                self.generator.recycle(token.0);
+                continue
+            }
+
+            self.lines.insert(token, line::Connection::new(sock, self.config.max_message_size));
+            self.activity.insert(token, Activity::new());
+            self.addresses.insert(token, ip);
+            *self.per_ip.entry(ip).or_insert(0) += 1;
+        }
+    }
+
+    fn line(&mut self, token: Token, readable: bool, writable: bool) {
+        let connection = match self.lines.get_mut(&token) {
+            Some(connection) => connection,
+            None => return
+        };
+
+        if readable {
+            let result = connection.read();
+
+            match result {
+                Ok(lines) => {
+                    if let Some(activity) = self.activity.get_mut(&token) {
+                        activity.seen = Instant::now();
+                    }
+
+                    for text in lines {
+                        // empty lines keep the connection alive
+                        if text.trim().is_empty() {
+                            continue
+                        }
+
+                        if self.closing.contains_key(&token) {
+                            log::debug!("ignoring request on closing {}", token.0);
+                            continue
+                        }
+
+                        self.events.push(Event{
+                            token,
+                            kind: transport::parse(&text)
+                        });
+                    }
+
+                    // the requests sent before hanging up are still handled, like on tokio
+                    if connection.eof() {
+                        log::debug!("connection closed on {}", token.0);
+                        self.events.push(Event{
+                            token,
+                            kind: EventKind::Close
+                        });
+                        return
+                    }
+                },
+                Err(e) => {
+                    log::info!("closing {}: {}", token.0, e);
+                    self.events.push(Event{
+                        token,
+                        kind: EventKind::Close
+                    });
+                    return
+                }
+            }
+        }
+
+        if writable {
+            self.flush_line(token);
+        }
+    }
+
+    fn flush_line(&mut self, token: Token) {
+        if let Some(connection) = self.lines.get_mut(&token) {
+            match connection.write() {
+                Ok(false) => {},
+                Ok(true) => {
+                    if self.closing.contains_key(&token) {
+                        self.events.push(Event{
+                            token,
+                            kind: EventKind::Close
+                        });
+                    }
+                },
+                Err(e) => {
+                    log::debug!("write error on {}: {}", token.0, e);
+                    self.events.push(Event{
+                        token,
+                        kind: EventKind::Close
+                    });
+                }
+            }
+        }
+    }
+
+    fn http(&mut self, token: Token, readable: bool, writable: bool) {
+        let connection = match self.http.get_mut(&token) {
+            Some(connection) => connection,
+            None => return
+        };
+
+        if readable && !connection.responded() {
+            match connection.read() {
+                Ok(Some(path)) => {
+                    log::debug!("http request {} on {}", path, token.0);
+                    self.events.push(Event{
+                        token,
+                        kind: EventKind::Http(path)
+                    });
+                },
+                Ok(None) => {},
+                Err(e) => {
+                    log::debug!("http read error on {}: {}", token.0, e);
+                    self.discard(token);
+                }
+            }
+            return
+        }
+
+        if writable {
+            self.flush_http(token);
+        }
+    }
+
+    fn flush_http(&mut self, token: Token) {
+        if let Some(connection) = self.http.get_mut(&token) {
+            match connection.write() {
+                Ok(false) => {},
+                Ok(true) => self.discard(token),
+                Err(e) => {
+                    log::debug!("http write error on {}: {}", token.0, e);
+                    self.discard(token);
+                }
+            }
+        }
+    }
+
+    fn accept_tls(&mut self, token: Token, result: Result<TlsStream<TcpStream>, TlsHandshakeError<TcpStream>>) {
+        match result {
+            Ok(stream) => {
+                log::debug!("tls handshake done on socket {}", token.0);
+                self.start_handshake(token, Socket::Tls(stream));
+            },
+            Err(TlsHandshakeError::WouldBlock(stream)) => {
+                self.tls.insert(token, stream);
+            },
+            Err(TlsHandshakeError::Failure(error)) => {
+                log::error!("tls handshake error: {}", error);
+                self.discard(token);
+            }
+        }
+    }
+
+    fn tls_handshake(&mut self, token: Token) {
+        if let Some(stream) = self.tls.remove(&token) {
+            log::debug!("tls handshake on socket {}", token.0);
+            self.accept_tls(token, stream.handshake());
+        }
+    }
+
+    fn start_handshake(&mut self, token: Token, socket: Socket) {
+        let check = Check::new(self.config.upgrade.clone(), self.refused.remove(&token));
+        let handshake = ServerHandshake::start(socket, check, Some(self.config.websocket()));
+        self.handshakes.insert(token, handshake);
+    }
+
+    // drops a connection which never reached the server, its token is free right away
+    fn discard(&mut self, token: Token) {
+        self.remove(token);
+        self.release(token);
     }
 
     pub fn reregister(&mut self) -> io::Result<()> {
-        for (token, _) in self.responses.iter() {
-            if let Some(ws) = self.ws.get_mut(&token) {
-                log::debug!("reregister write {}", token.0);
-                self.poll.registry().reregister(ws.get_mut(), *token, Interest::READABLE | Interest::WRITABLE)?;
+        for (token, ws) in self.ws.iter_mut() {
+            let queued = self.responses.get(token).is_some_and(|r| !r.is_empty());
+            let writable = queued || self.pending.contains(token);
+
+            if writable == self.writable.contains(token) {
+                continue
             }
+
+            let interest = if writable {
+                log::debug!("reregister write {}", token.0);
+                self.writable.insert(*token);
+                Interest::READABLE | Interest::WRITABLE
+            } else {
+                log::debug!("reregister read {}", token.0);
+                self.writable.remove(token);
+                Interest::READABLE
+            };
+
+            self.poll.registry().reregister(ws.get_mut().get_mut(), *token, interest)?;
         }
         Ok(())
     }
 
-    fn read(&mut self, token: Token) {
-        if let Some(stream) = self.sockets.remove(&token) {
-            log::debug!("accepting socket {}", token.0);
-            match accept(stream) {
+    fn handshake(&mut self, token: Token) {
+        if let Some(handshake) = self.handshakes.remove(&token) {
+            log::debug!("handshake on socket {}", token.0);
+            match handshake.handshake() {
                 Ok(ws) => {
-                     self.ws.insert(token, ws);
+                    log::debug!("handshake done on socket {}", token.0);
+                    self.ws.insert(token, ws);
                 },
-                Err(error) => {
-                    log::error!("accept error: {}", error);
-                    self.remove(token);
+                Err(HandshakeError::Interrupted(handshake)) => {
+                    self.handshakes.insert(token, handshake);
+                },
+                Err(HandshakeError::Failure(WsError::Http(response))) => {
+                    log::info!("handshake refused on {}: {}", token.0, response.status());
+                    self.discard(token);
+                },
+                Err(HandshakeError::Failure(error)) => {
+                    log::error!("handshake error: {}", error);
+                    self.discard(token);
                 }
             }
-            return
         }
+    }
 
+    fn read(&mut self, token: Token) {
         if let Some(ws) = self.ws.get_mut(&token) {
-            loop {
-                log::debug!("reading request on {}", token.0);
-                match ws.read_message() {
-                    Ok(message) => match message {
-                        Message::Text(msg) => {
-                            match Request::from_str(&msg) {
-                                Ok(request) => self.events.push(Event{
-                                    token: token,
-                                    kind: EventKind::Request(request)
-                                }),
-                                Err(error) => self.events.push(Event{
-                                    token: token,
-                                    kind: EventKind::Error(error)
-                                }),
-                            }
-                        },
-                        Message::Close(_) => {
-                            self.events.push(Event{
-                                token: token,
-                                kind: EventKind::Close
-                            });
-                            return
-                        }
-                        _ => {}
-                    },
-                    Err(error) => match error {
-                        WsError::Io(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                            log::debug!("finished reading requests on {}", token.0);
-                            return
-                        },
-                        _ => {
-                            log::error!("read error: {}", error);
-                            self.events.push(Event{
-                                token: token,
-                                kind: EventKind::Close
-                            });
-                            return
-                        }
-                    }
+            let closing = self.closing.contains_key(&token);
+            if read_messages(ws, token, closing, &mut self.events) {
+                if let Some(activity) = self.activity.get_mut(&token) {
+                    activity.seen = Instant::now();
                 }
             }
         }
     }
 
-    pub fn remove(&mut self, token: Token) {
-        if let Some(mut s) = self.sockets.remove(&token) {
+    fn write(&mut self, token: Token) {
+        let ws = match self.ws.get_mut(&token) {
+            Some(ws) => ws,
+            None => return
+        };
+
+        match write_messages(ws, token, self.responses.get_mut(&token)) {
+            Flush::Done => {
+                self.pending.remove(&token);
+            },
+            Flush::Blocked => {
+                self.pending.insert(token);
+            },
+            Flush::Closed => {
+                self.pending.remove(&token);
+                self.events.push(Event{
+                    token,
+                    kind: EventKind::Close
+                });
+            }
+        }
+    }
+
+}
+
+impl Transport for Stream {
+
+    fn waker(&self) -> io::Result<Arc<dyn transport::Waker>> {
+        Ok(Arc::new(Waker::new(self.poll.registry(), WAKER)?))
+    }
+
+    fn poll(&mut self) -> io::Result<Vec<Event>> {
+        self.reregister()?;
+
+        // edge triggered listeners get no new event for connections still queued
+        for listener in mem::take(&mut self.backlog) {
+            self.accept(listener);
+        }
+
+        let mut events = Events::with_capacity(128);
+
+        log::debug!("polling");
+        match self.poll.poll(&mut events, Some(TICK)) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+            result => result?
+        }
+
+        for event in events.iter() {
+            let token = event.token();
+            log::debug!("event: {:?}", event);
+
+            if token == LISTENER || token == ADMIN || token == TCP {
+                self.backlog.remove(&token);
+                self.accept(token);
+                continue
+            }
+
+            if token == WAKER {
+                continue
+            }
+
+            if self.lines.contains_key(&token) {
+                self.line(token, event.is_readable(), event.is_writable());
+                continue
+            }
+
+            if self.http.contains_key(&token) {
+                self.http(token, event.is_readable(), event.is_writable());
+                continue
+            }
+
+            if token == SIGNAL {
+                for signal in self.signals.pending() {
+                    log::info!("received signal {}", signal);
+                    self.events.push(Event{
+                        token,
+                        kind: EventKind::Shutdown
+                    });
+                }
+                continue
+            }
+
+            if self.tls.contains_key(&token) {
+                self.tls_handshake(token);
+            }
+
+            if self.handshakes.contains_key(&token) {
+                self.handshake(token);
+            }
+
+            if event.is_readable() {
+                self.read(token);
+            }
+
+            if event.is_writable() {
+                self.write(token);
+            }
+        }
+
+        self.heartbeat();
+
+        Ok(self.events.drain(..).collect())
+    }
+
+    fn shutdown(&mut self, timeout: Duration) -> io::Result<()> {
+        self.poll.registry().deregister(&mut self.listener)?;
+
+        if let Some(admin) = self.admin.as_mut() {
+            self.poll.registry().deregister(admin)?;
+        }
+
+        if let Some(tcp) = self.tcp.as_mut() {
+            self.poll.registry().deregister(tcp)?;
+        }
+
+        let tokens: Vec<Token> = self.tls.keys()
+            .chain(self.handshakes.keys())
+            .chain(self.http.keys())
+            .copied()
+            .collect();
+
+        for token in tokens {
+            self.remove(token);
+        }
+
+        let tokens: Vec<Token> = self.ws.keys().chain(self.lines.keys()).copied().collect();
+        for token in tokens {
+            self.close(token, CloseCode::Away, "server shutting down");
+        }
+
+        let deadline = Instant::now() + timeout;
+        while !(self.ws.is_empty() && self.lines.is_empty()) && Instant::now() < deadline {
+            for event in self.poll()? {
+                if let EventKind::Close = event.kind {
+                    self.remove(event.token);
+                }
+            }
+        }
+
+        log::info!("closing {} remaining connections", self.ws.len() + self.lines.len());
+        let tokens: Vec<Token> = self.ws.keys().chain(self.lines.keys()).copied().collect();
+        for token in tokens {
+            self.remove(token);
+        }
+
+        Ok(())
+    }
+
+    fn close(&mut self, token: Token, code: CloseCode, reason: &str) {
+        if self.closing.contains_key(&token) {
+            return
+        }
+
+        // line connections have no close frame, they are closed once flushed
+        if self.lines.contains_key(&token) {
+            log::debug!("closing {}: {}", token.0, reason);
+            self.closing.insert(token, Instant::now());
+            self.flush_line(token);
+            return
+        }
+
+        if !self.ws.contains_key(&token) {
+            return
+        }
+
+        log::debug!("closing {}: {}", token.0, reason);
+        self.push(token, Message::Close(Some(CloseFrame {
+            code,
+            reason: reason.to_string().into(),
+        })));
+        self.closing.insert(token, Instant::now());
+    }
+
+    fn respond(&mut self, token: Token, status: u16, reason: &str, content_type: &str, body: &str) {
+        if let Some(connection) = self.http.get_mut(&token) {
+            connection.respond(status, reason, content_type, body);
+            self.flush_http(token);
+        }
+    }
+
+    fn connections(&self) -> usize {
+        self.tls.len() + self.handshakes.len() + self.ws.len() + self.lines.len()
+    }
+
+    fn address(&self, token: Token) -> Option<IpAddr> {
+        self.addresses.get(&token).copied()
+    }
+
+    fn remove(&mut self, token: Token) {
+        let result = if let Some(mut connection) = self.http.remove(&token) {
+            log::debug!("removing http socket {}", token.0);
+            self.poll.registry().deregister(&mut connection.socket)
+        } else if let Some(mut stream) = self.tls.remove(&token) {
+            log::debug!("removing tls socket {}", token.0);
+            self.poll.registry().deregister(stream.get_mut())
+        } else if let Some(mut handshake) = self.handshakes.remove(&token) {
             log::debug!("removing socket {}", token.0);
-            self.poll.registry().deregister(&mut s).unwrap();
+            self.poll.registry().deregister(handshake.get_mut().get_mut().get_mut())
+        } else if let Some(mut connection) = self.lines.remove(&token) {
+            log::debug!("removing tcp socket {}", token.0);
+            self.poll.registry().deregister(&mut connection.socket)
         } else if let Some(mut ws) = self.ws.remove(&token) {
             log::debug!("removing websocket {}", token.0);
-            self.poll.registry().deregister(ws.get_mut()).unwrap();
+            self.poll.registry().deregister(ws.get_mut().get_mut())
+        } else {
+            Ok(())
+        };
+
+        if let Err(error) = result {
+            log::error!("deregister error on {}: {}", token.0, error);
+        }
+
+        if let Some(ip) = self.addresses.remove(&token) {
+            if let Some(count) = self.per_ip.get_mut(&ip) {
+                *count -= 1;
+                if *count == 0 {
+                    self.per_ip.remove(&ip);
+                }
+            }
+        }
+
+        if self.activity.remove(&token).is_some() {
+            self.refused.remove(&token);
+            self.closing.remove(&token);
+            self.responses.remove(&token);
+            self.pending.remove(&token);
+            self.writable.remove(&token);
+            self.retired.insert(token);
         }
-        self.generator.recycle(token.0);
     }
 
-    pub fn push(&mut self, token: Token, response: Message) {
-        log::debug!("push: {:?}", response);
-        self.responses.push((token, response))
+    fn release(&mut self, token: Token) {
+        if self.retired.remove(&token) {
+            self.generator.recycle(token.0);
+        }
     }
 
-    pub fn append(&mut self, mut responses: Vec<(Token, Message)>) {
-        log::debug!("append: {:?}", responses);
-        self.responses.append(&mut responses)
+    fn push(&mut self, token: Token, response: Message) {
+        log::debug!("push: {:?}", response);
+
+        if self.closing.contains_key(&token) {
+            log::debug!("dropping response to closing {}", token.0);
+            return
+        }
+
+        // responses computed by a worker can arrive after the connection is gone,
+        // they must not be left queued for the next connection reusing the token
+        if !self.ws.contains_key(&token) && !self.lines.contains_key(&token) {
+            log::debug!("dropping response to closed {}", token.0);
+            return
+        }
+
+        if let Some(connection) = self.lines.get_mut(&token) {
+            connection.queue(&response);
+
+            if connection.queued() > self.config.max_queue {
+                log::warn!("outbound queue full on {}, disconnecting slow consumer", token.0);
+                self.events.push(Event{
+                    token,
+                    kind: EventKind::Close
+                });
+            } else {
+                self.flush_line(token);
+            }
+            return
+        }
+
+        let queue = self.responses.entry(token).or_default();
+        if !enqueue(queue, response, self.config.max_queue) {
+            log::warn!("outbound queue full on {}, disconnecting slow consumer", token.0);
+            self.events.push(Event{
+                token,
+                kind: EventKind::Close
+            });
+        }
     }
 
-    fn write(&mut self, token: Token) -> io::Result<()> {
-        let (filtered, responses) = self.responses
-            .drain(..)
-            .partition(|(t, _)| *t == token);
+}
+
+pub enum Flush {
+    Done,
+    Blocked,
+    Closed,
+}
 
-        self.responses = responses;
+// the websocket side of a connection works on any socket, so it can be driven without one
 
-        if let Some(ws) = self.ws.get_mut(&token) {
-            for (_, response) in filtered {
-                if let Err(error) = ws.write_message(response) {
-                    log::error!("write error: {}", error);
-                    self.events.push(Event{
-                        token: token,
+// reads every message available, requests and closes go to events,
+// returns true if anything was read
+pub fn read_messages<S: io::Read + Write>(ws: &mut WebSocket<S>, token: Token, closing: bool, events: &mut Vec<Event>) -> bool {
+    let mut seen = false;
+
+    loop {
+        log::debug!("reading request on {}", token.0);
+        let result = ws.read_message();
+
+        if result.is_ok() {
+            seen = true;
+        }
+
+        match result {
+            Ok(message) => match message {
+                Message::Text(_) if closing => {
+                    log::debug!("ignoring request on closing {}", token.0);
+                },
+                Message::Text(msg) => {
+                    events.push(Event{
+                        token,
+                        kind: transport::parse(&msg)
+                    })
+                },
+                Message::Close(_) => {
+                    let _ = ws.write_pending();
+                    events.push(Event{
+                        token,
+                        kind: EventKind::Close
+                    });
+                    return seen
+                }
+                _ => {}
+            },
+            Err(error) => match error {
+                WsError::Io(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    log::debug!("finished reading requests on {}", token.0);
+                    return seen
+                },
+                WsError::Capacity(e) => {
+                    log::info!("closing {}: {}", token.0, e);
+                    let _ = ws.close(Some(CloseFrame {
+                        code: CloseCode::Size,
+                        reason: "message too big".into(),
+                    }));
+                    let _ = ws.write_pending();
+                    events.push(Event{
+                        token,
+                        kind: EventKind::Close
+                    });
+                    return seen
+                },
+                WsError::ConnectionClosed => {
+                    log::debug!("connection closed on {}", token.0);
+                    events.push(Event{
+                        token,
                         kind: EventKind::Close
                     });
+                    return seen
+                },
+                _ => {
+                    log::error!("read error: {}", error);
+                    events.push(Event{
+                        token,
+                        kind: EventKind::Close
+                    });
+                    return seen
                 }
             }
-            self.poll.registry().reregister(ws.get_mut(), token, Interest::READABLE)?;
         }
+    }
+}
 
-        Ok(())
+// writes queued messages for as long as the socket takes them
+pub fn write_messages<S: io::Read + Write>(ws: &mut WebSocket<S>, token: Token, queue: Option<&mut VecDeque<Message>>) -> Flush {
+    let mut result = ws.write_pending();
+
+    if result.is_ok() {
+        if let Some(queue) = queue {
+            while let Some(response) = queue.pop_front() {
+                result = ws.write_message(response);
+
+                if let Err(WsError::SendQueueFull(response)) = result {
+                    queue.push_front(response);
+                    result = Err(WsError::Io(io::ErrorKind::WouldBlock.into()));
+                }
+
+                if result.is_err() {
+                    break
+                }
+            }
+        }
     }
 
+    if result.is_ok() {
+        result = ws.write_pending();
+    }
+
+    match result {
+        Ok(_) => Flush::Done,
+        Err(WsError::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {
+            log::debug!("write would block on {}", token.0);
+            Flush::Blocked
+        },
+        Err(WsError::ConnectionClosed) => {
+            log::debug!("connection closed on {}", token.0);
+            Flush::Closed
+        },
+        Err(error) => {
+            log::error!("write error: {}", error);
+            Flush::Closed
+        }
+    }
+}
+
+// errors about a connection which went away before it was accepted, not about the listener
+fn aborted(error: &io::Error) -> bool {
+    matches!(error.kind(), io::ErrorKind::ConnectionAborted | io::ErrorKind::ConnectionReset | io::ErrorKind::Interrupted)
+}
+
+// queues a message, false once the queue overflows and the slow consumer has to go
+pub fn enqueue(queue: &mut VecDeque<Message>, message: Message, max_queue: usize) -> bool {
+    queue.push_back(message);
+
+    if queue.len() > max_queue {
+        queue.clear();
+        return false
+    }
+    true
 }
@@ -0,0 +1,86 @@
+use crate::request::Request;
+use crate::error::RequestError;
+use crate::response::{Envelope, Response};
+use mio::Token;
+use tungstenite::Message;
+use tungstenite::protocol::frame::coding::CloseCode;
+use std::io;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct Event {
+    pub token: Token,
+    pub kind: EventKind,
+}
+
+#[derive(Debug)]
+pub enum EventKind {
+    Request( Request),
+    Error(RequestError),
+    Http(String),
+    Close,
+    Shutdown
+}
+
+// wakes a transport blocked in poll from another thread
+pub trait Waker: Send + Sync {
+    fn wake(&self) -> io::Result<()>;
+}
+
+impl Waker for mio::Waker {
+    fn wake(&self) -> io::Result<()> {
+        mio::Waker::wake(self)
+    }
+}
+
+pub trait Transport {
+    fn poll(&mut self) -> io::Result<Vec<Event>>;
+    fn waker(&self) -> io::Result<Arc<dyn Waker>>;
+    fn push(&mut self, token: Token, response: Message);
+    fn close(&mut self, token: Token, code: CloseCode, reason: &str);
+    fn remove(&mut self, token: Token);
+    // the server holds on to removed tokens until no reply for them is left in flight,
+    // transports which never reuse tokens have nothing to do
+    fn release(&mut self, _token: Token) {}
+    fn respond(&mut self, token: Token, status: u16, reason: &str, content_type: &str, body: &str);
+    fn connections(&self) -> usize;
+    fn address(&self, token: Token) -> Option<IpAddr>;
+    fn shutdown(&mut self, timeout: Duration) -> io::Result<()>;
+
+    fn send(&mut self, envelope: Envelope) {
+        let message = match encode(&envelope.response) {
+            Some(message) => message,
+            None => return
+        };
+        for token in envelope.recipients {
+            self.push(token, message.clone());
+        }
+    }
+
+    fn send_all(&mut self, envelopes: Vec<Envelope>) {
+        for envelope in envelopes {
+            self.send(envelope);
+        }
+    }
+}
+
+// a response which cannot be encoded is dropped, clients only ever get valid JSON
+pub fn encode(response: &Response) -> Option<Message> {
+    match serde_json::to_string(response) {
+        Ok(text) => Some(Message::Text(text)),
+        Err(e) => {
+            log::error!("could not encode {:?}: {}", response, e);
+            None
+        }
+    }
+}
+
+pub fn parse(text: &str) -> EventKind {
+    match Request::from_str(text) {
+        Ok(request) => EventKind::Request(request),
+        Err(error) => EventKind::Error(error)
+    }
+}
@@ -0,0 +1,99 @@
+use serde::Deserialize;
+use tungstenite::handshake::server::{Callback, Request, Response, ErrorResponse};
+use tungstenite::http::{StatusCode, HeaderValue};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Upgrade {
+    pub path: Option<String>,
+    pub origins: Vec<String>,
+    pub protocols: Vec<String>,
+}
+
+impl Default for Upgrade {
+    fn default() -> Upgrade {
+        Upgrade {
+            path: Some("/api".to_string()),
+            origins: Vec::new(),
+            protocols: Vec::new(),
+        }
+    }
+}
+
+pub struct Check {
+    upgrade: Upgrade,
+    refuse: Option<&'static str>,
+}
+
+impl Check {
+
+    pub fn new(upgrade: Upgrade, refuse: Option<&'static str>) -> Check {
+        Check {
+            upgrade,
+            refuse,
+        }
+    }
+
+}
+
+impl Callback for Check {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        if let Some(reason) = self.refuse {
+            log::info!("upgrade rejected: {}", reason);
+            return Err(reject(StatusCode::SERVICE_UNAVAILABLE, reason))
+        }
+
+        self.upgrade.on_request(request, response)
+    }
+}
+
+impl Callback for Upgrade {
+    fn on_request(self, request: &Request, mut response: Response) -> Result<Response, ErrorResponse> {
+        if let Some(path) = &self.path {
+            if request.uri().path() != path {
+                log::info!("upgrade rejected: unknown path {}", request.uri().path());
+                return Err(reject(StatusCode::NOT_FOUND, "unknown path"))
+            }
+        }
+
+        if !self.origins.is_empty() {
+            let origin = request.headers()
+                .get("Origin")
+                .and_then(|h| h.to_str().ok())
+                .unwrap_or("");
+
+            if !self.origins.iter().any(|o| o == origin) {
+                log::info!("upgrade rejected: origin {:?} not allowed", origin);
+                return Err(reject(StatusCode::FORBIDDEN, "origin not allowed"))
+            }
+        }
+
+        if !self.protocols.is_empty() {
+            let protocol = request.headers()
+                .get_all("Sec-WebSocket-Protocol")
+                .iter()
+                .filter_map(|h| h.to_str().ok())
+                .flat_map(|h| h.split(','))
+                .map(|p| p.trim())
+                .find(|p| self.protocols.iter().any(|s| s == p));
+
+            match protocol.and_then(|p| HeaderValue::from_str(p).ok()) {
+                Some(value) => {
+                    response.headers_mut().insert("Sec-WebSocket-Protocol", value);
+                },
+                None => {
+                    log::info!("upgrade rejected: no supported subprotocol");
+                    return Err(reject(StatusCode::BAD_REQUEST, "unsupported subprotocol"))
+                }
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+fn reject(status: StatusCode, reason: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(reason.to_string()));
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,265 @@
+use crate::room::{self, Room, Snapshot};
+use crate::response::{Envelope, Response};
+use crate::request::Request;
+use crate::error::RoomError;
+use crate::transport::Waker;
+use codename_engine::board::WordMap;
+use codename_engine::spymaster::{Clues, Spymaster};
+use mio::Token;
+use uuid::Uuid;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+pub enum Command {
+    Insert(Box<Room>),
+    Join(Uuid, Token, Request),
+    Request(Uuid, Token, Request),
+    Leave(Uuid, Token),
+    // acknowledged once every command queued before it for the token was handled
+    Release(Token),
+    Clues(Uuid, Arc<Clues>),
+    Shutdown(Response),
+}
+
+#[derive(Debug)]
+pub enum Reply {
+    Responses(Vec<Envelope>),
+    Error(Token, RoomError),
+    // the join was refused, the token is not part of the room
+    Rejected(Uuid, Token, RoomError),
+    State(Uuid, &'static str),
+    // a start request succeeded
+    Started,
+    Closed(Uuid, Vec<Token>),
+    Released(Token),
+    // the rooms of the worker as it shuts down
+    Snapshot(Vec<Snapshot>),
+}
+
+type Job = (Uuid, Arc<Spymaster>, WordMap);
+
+pub struct Worker {
+    pub rooms: usize,
+    sender: Sender<Command>,
+    handle: JoinHandle<()>,
+}
+
+impl Worker {
+
+    pub fn spawn(index: usize, max_players: Option<usize>, replies: Sender<Reply>, waker: Arc<dyn Waker>) -> io::Result<Worker> {
+        let (sender, receiver) = mpsc::channel();
+        let (bot, jobs) = mpsc::channel();
+
+        // clues take a while to compute, the rooms of the worker keep playing meanwhile
+        let commands = sender.clone();
+        thread::Builder::new()
+            .name(format!("worker-{}-bot", index))
+            .spawn(move || compute_clues(jobs, commands))?;
+
+        let handle = thread::Builder::new()
+            .name(format!("worker-{}", index))
+            .spawn(move || {
+                let mut rooms = Rooms {
+                    rooms: HashMap::new(),
+                    max_players,
+                    replies,
+                    waker,
+                    bot,
+                    computing: HashSet::new(),
+                };
+                rooms.run(receiver)
+            })?;
+
+        Ok(Worker {
+            rooms: 0,
+            sender,
+            handle,
+        })
+    }
+
+    pub fn send(&self, command: Command) {
+        if self.sender.send(command).is_err() {
+            log::error!("worker {:?} is gone", self.handle.thread().name());
+        }
+    }
+
+    pub fn join(self) {
+        drop(self.sender);
+        if self.handle.join().is_err() {
+            log::error!("worker panicked");
+        }
+    }
+
+}
+
+struct Rooms {
+    rooms: HashMap<Uuid, Room>,
+    max_players: Option<usize>,
+    replies: Sender<Reply>,
+    waker: Arc<dyn Waker>,
+    bot: Sender<Job>,
+    computing: HashSet<Uuid>,
+}
+
+impl Rooms {
+
+    fn run(&mut self, receiver: Receiver<Command>) {
+        for command in receiver {
+            let shutdown = matches!(command, Command::Shutdown(_));
+
+            let room = match &command {
+                Command::Join(id, _, _) | Command::Request(id, _, _) | Command::Clues(id, _) => Some(*id),
+                _ => None
+            };
+
+            let replies = self.handle(command);
+            if let Some(id) = room {
+                self.want_clues(id);
+            }
+            for reply in replies {
+                let _ = self.replies.send(reply);
+            }
+            if let Err(e) = self.waker.wake() {
+                log::error!("could not wake stream: {}", e);
+            }
+
+            if shutdown {
+                break
+            }
+        }
+    }
+
+    fn handle(&mut self, command: Command) -> Vec<Reply> {
+        match command {
+            Command::Insert(room) => {
+                self.rooms.insert(room.id, *room);
+                Vec::new()
+            },
+            Command::Join(id, token, request) => {
+                match self.join(id, token, &request) {
+                    Ok(replies) => replies,
+                    Err(error) => vec![Reply::Rejected(id, token, error)]
+                }
+            },
+            Command::Request(id, token, request) => {
+                match self.request(id, token, &request) {
+                    Ok(replies) => replies,
+                    Err(error) => vec![Reply::Error(token, error)]
+                }
+            },
+            Command::Leave(id, token) => self.leave(id, token),
+            Command::Release(token) => vec![Reply::Released(token)],
+            Command::Clues(id, clues) => self.clues(id, clues),
+            Command::Shutdown(response) => {
+                let mut replies: Vec<Reply> = self.rooms.values()
+                    .map(|room| Reply::Responses(room.resolve(room.broadcast(response.clone()))))
+                    .collect();
+                replies.push(Reply::Snapshot(self.rooms.values().map(Room::snapshot).collect()));
+                replies
+            }
+        }
+    }
+
+    fn join(&mut self, id: Uuid, token: Token, request: &Request) -> Result<Vec<Reply>, RoomError> {
+        let room = self.rooms.get(&id).ok_or(RoomError::NotFound(id))?;
+
+        if let Request::Join(j) = request {
+            room.check_password(&j.password)?;
+        }
+
+        if let Some(max) = self.max_players {
+            if room.players.len() >= max {
+                return Err(RoomError::Limit("players"))
+            }
+        }
+
+        log::debug!("{} - adding token {}", id, token.0);
+        self.request(id, token, request)
+    }
+
+    fn request(&mut self, id: Uuid, token: Token, request: &Request) -> Result<Vec<Reply>, RoomError> {
+        let room = self.rooms.get_mut(&id).ok_or(RoomError::NotFound(id))?;
+
+        log::debug!("{} - handle token {} request {:?}", room.id, token.0, request);
+        let before = room.state();
+        let responses = room.handle(token, request)?;
+        let after = room.state();
+
+        let mut replies = Vec::new();
+        if let Request::Start(_) = request {
+            replies.push(Reply::Started);
+        }
+        replies.push(Reply::Responses(room.resolve(responses)));
+        if before != after {
+            replies.push(Reply::State(id, after));
+        }
+        Ok(replies)
+    }
+
+    fn clues(&mut self, id: Uuid, clues: Arc<Clues>) -> Vec<Reply> {
+        self.computing.remove(&id);
+
+        let room = match self.rooms.get_mut(&id) {
+            Some(room) => room,
+            None => return Vec::new()
+        };
+
+        let before = room.state();
+        let responses = room.set_clues(clues);
+        let after = room.state();
+
+        let mut replies = vec![Reply::Responses(room.resolve(responses))];
+        if before != after {
+            replies.push(Reply::State(id, after));
+        }
+        replies
+    }
+
+    fn want_clues(&mut self, id: Uuid) {
+        if self.computing.contains(&id) {
+            return
+        }
+
+        if let Some((spymaster, words)) = self.rooms.get(&id).and_then(Room::wants_clues) {
+            log::debug!("{} - computing clues", id);
+            if self.bot.send((id, spymaster, words)).is_ok() {
+                self.computing.insert(id);
+            }
+        }
+    }
+
+    fn leave(&mut self, id: Uuid, token: Token) -> Vec<Reply> {
+        let mut replies = Vec::new();
+
+        if let Some(room) = self.rooms.get_mut(&id) {
+            let before = room.state();
+            let responses = room.remove_player(token);
+            replies.push(Reply::Responses(room.resolve(responses)));
+            if before != room.state() {
+                replies.push(Reply::State(id, room.state()));
+            }
+
+            if !room.is_alive(token) {
+                log::info!("{} - closing room", id);
+                let tokens = room.game.players().copied().map(room::player_token).collect();
+                self.rooms.remove(&id);
+                replies.push(Reply::Closed(id, tokens));
+            }
+        }
+
+        replies
+    }
+
+}
+
+fn compute_clues(jobs: Receiver<Job>, commands: Sender<Command>) {
+    for (id, spymaster, words) in jobs {
+        let clues = spymaster.clues(&words);
+        if commands.send(Command::Clues(id, Arc::new(clues))).is_err() {
+            break
+        }
+    }
+}
@@ -0,0 +1,129 @@
+#![cfg(feature = "async")]
+use codename::asyncstream::AsyncStream;
+use codename::server::{self, Server};
+use codename::stream;
+use codename_engine::board::BoardSet;
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+const BOARDS: &str = r#"{
+    "words": {"en": ["w0", "w1", "w2", "w3", "w4", "w5", "w6", "w7", "w8", "w9",
+                     "w10", "w11", "w12", "w13", "w14", "w15", "w16", "w17", "w18", "w19",
+                     "w20", "w21", "w22", "w23", "w24"]},
+    "tiles": [[["red", "red", "red", "red", "red"],
+               ["red", "red", "red", "red", "blue"],
+               ["blue", "blue", "blue", "blue", "blue"],
+               ["blue", "blue", "neutral", "neutral", "neutral"],
+               ["neutral", "neutral", "neutral", "neutral", "death"]]]
+}"#;
+
+struct Addresses {
+    ws: SocketAddr,
+    tcp: SocketAddr,
+    admin: SocketAddr,
+}
+
+// the listeners are bound by the transport, so the ports are picked beforehand
+fn free_address() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+}
+
+// the server keeps running on its own thread until the end of the test process
+fn start() -> Addresses {
+    let addresses = Addresses {
+        ws: free_address(),
+        tcp: free_address(),
+        admin: free_address(),
+    };
+
+    let config = stream::Config {
+        tcp: Some(addresses.tcp),
+        admin: Some(addresses.admin),
+        ..stream::Config::default()
+    };
+    let transport = AsyncStream::new(addresses.ws, config).expect("could not start the transport");
+    let boardset: BoardSet = serde_json::from_str(BOARDS).expect("invalid test boards");
+
+    thread::spawn(move || {
+        let mut server = Server::new(Arc::new(boardset), transport, server::Config::default()).expect("could not start the server");
+        server.run().expect("server failed");
+    });
+
+    addresses
+}
+
+fn ws(addr: SocketAddr) -> WebSocket<TcpStream> {
+    let socket = TcpStream::connect(addr).unwrap();
+    socket.set_read_timeout(Some(TIMEOUT)).unwrap();
+    let (ws, _) = tungstenite::client(format!("ws://{}/api", addr), socket).expect("handshake failed");
+    ws
+}
+
+fn ws_recv(ws: &mut WebSocket<TcpStream>, kind: &str) -> Value {
+    loop {
+        match ws.read_message().expect("no response") {
+            Message::Text(text) => {
+                let response: Value = serde_json::from_str(&text).unwrap();
+                if response["response"] == kind {
+                    return response
+                }
+            },
+            Message::Close(_) => panic!("closed while waiting for {}", kind),
+            _ => {}
+        }
+    }
+}
+
+fn line_recv(reader: &mut BufReader<TcpStream>, kind: &str) -> Value {
+    loop {
+        let mut line = String::new();
+        assert!(reader.read_line(&mut line).expect("no response") > 0, "closed while waiting for {}", kind);
+        let response: Value = serde_json::from_str(&line).unwrap();
+        if response["response"] == kind {
+            return response
+        }
+    }
+}
+
+fn players(response: &Value) -> usize {
+    response["room"]["players"].as_array().expect("no players").len()
+}
+
+#[test]
+fn transports() {
+    let addresses = start();
+
+    let mut alice = ws(addresses.ws);
+    alice.write_message(Message::Text(r#"{"request": "room", "name": "alice", "language": "en"}"#.into())).unwrap();
+    let room = ws_recv(&mut alice, "room");
+    let code = room["room"]["code"].as_str().expect("no room code").to_string();
+
+    let mut bob = TcpStream::connect(addresses.tcp).unwrap();
+    bob.set_read_timeout(Some(TIMEOUT)).unwrap();
+    let mut bob_reader = BufReader::new(bob.try_clone().unwrap());
+    writeln!(bob, r#"{{"request": "join", "code": "{}", "name": "bob"}}"#, code).unwrap();
+    assert_eq!(players(&line_recv(&mut bob_reader, "room")), 2);
+    assert_eq!(players(&ws_recv(&mut alice, "room")), 2);
+
+    alice.write_message(Message::Text(r#"{"request": "chat", "channel": "all", "message": "hello"}"#.into())).unwrap();
+    let chat = line_recv(&mut bob_reader, "chat");
+    assert_eq!(chat["messages"][0]["message"], "hello");
+
+    let mut admin = TcpStream::connect(addresses.admin).unwrap();
+    admin.set_read_timeout(Some(TIMEOUT)).unwrap();
+    admin.write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut health = String::new();
+    admin.read_to_string(&mut health).unwrap();
+    assert!(health.starts_with("HTTP/1.1 200"), "unexpected health response {:?}", health);
+
+    drop(bob_reader);
+    drop(bob);
+    assert_eq!(players(&ws_recv(&mut alice, "room")), 1);
+}
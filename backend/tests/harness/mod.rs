@@ -0,0 +1,326 @@
+use codename::chat::Channel;
+use codename::memory::{Connection, Handle, Memory, Output};
+use codename::ratelimit;
+use codename::request::{self, Request};
+use codename::response::Response;
+use codename::room::View;
+use codename::server::{self, Server};
+use codename_engine::board::{BoardSet, TileMap};
+use codename_engine::spymaster::Spymaster;
+use codename_engine::team::Team;
+use std::sync::Arc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const QUIET: Duration = Duration::from_millis(100);
+
+// red starts with 9 tiles: rows 0 and 1 are red except (1, 4), the assassin is (4, 4)
+const BOARDS: &str = r#"{
+    "words": {"en": ["w0", "w1", "w2", "w3", "w4", "w5", "w6", "w7", "w8", "w9",
+                     "w10", "w11", "w12", "w13", "w14", "w15", "w16", "w17", "w18", "w19",
+                     "w20", "w21", "w22", "w23", "w24", "w25", "w26", "w27", "w28", "w29"]},
+    "tiles": [[["red", "red", "red", "red", "red"],
+               ["red", "red", "red", "red", "blue"],
+               ["blue", "blue", "blue", "blue", "blue"],
+               ["blue", "blue", "neutral", "neutral", "neutral"],
+               ["neutral", "neutral", "neutral", "neutral", "death"]]]
+}"#;
+
+// every board word has its own direction, shared only with its clue
+fn embeddings() -> String {
+    let mut embeddings = String::new();
+    for i in 0..30 {
+        let vector: Vec<&str> = (0..30).map(|j| if i == j { "1" } else { "0" }).collect();
+        embeddings.push_str(&format!("w{} {}\n", i, vector.join(" ")));
+        embeddings.push_str(&format!("{} {}\n", clue(&format!("w{}", i)), vector.join(" ")));
+    }
+    embeddings
+}
+
+// the only clue related to a word of the test boards, made of letters as clues must be
+pub fn clue(word: &str) -> String {
+    let i: u8 = word[1..].parse().expect("not a test board word");
+    format!("clue{}{}", (b'a' + i / 10) as char, (b'a' + i % 10) as char)
+}
+
+// a server running on its own thread behind the in-memory transport
+pub struct Harness {
+    handle: Handle,
+    server: Option<JoinHandle<Result<(), String>>>,
+}
+
+// a connection speaking typed requests and responses
+pub struct Client {
+    pub name: String,
+    connection: Connection,
+}
+
+impl Harness {
+
+    pub fn start() -> Harness {
+        Harness::with_config(server::Config {
+            workers: 2,
+            rate_limit: ratelimit::Config {
+                enabled: false,
+                ..ratelimit::Config::default()
+            },
+            ..server::Config::default()
+        })
+    }
+
+    pub fn with_spymaster() -> Harness {
+        let spymaster = Spymaster::from_reader(embeddings().as_bytes()).expect("invalid test embeddings");
+        Harness::with_config(server::Config {
+            workers: 2,
+            rate_limit: ratelimit::Config {
+                enabled: false,
+                ..ratelimit::Config::default()
+            },
+            spymaster: Some(Arc::new(spymaster)),
+            ..server::Config::default()
+        })
+    }
+
+    pub fn with_config(config: server::Config) -> Harness {
+        let boardset: BoardSet = serde_json::from_str(BOARDS).expect("invalid test boards");
+        let memory = Memory::new();
+        let handle = memory.handle();
+
+        let server = thread::spawn(move || {
+            let mut server = Server::new(Arc::new(boardset), memory, config).map_err(|e| e.to_string())?;
+            server.run().map_err(|e| e.to_string())
+        });
+
+        Harness {
+            handle,
+            server: Some(server),
+        }
+    }
+
+    pub fn connect(&self, name: &str) -> Client {
+        Client {
+            name: name.to_string(),
+            connection: self.handle.connect(),
+        }
+    }
+
+    // creates a room and has the other clients join it, draining every update
+    pub fn room(&self, names: &[&str]) -> Vec<Client> {
+        let clients: Vec<Client> = names.iter().map(|name| self.connect(name)).collect();
+
+        let code = clients[0].create().code;
+        for (i, client) in clients.iter().enumerate().skip(1) {
+            client.send(request::Request::Join(request::Join {
+                id: None,
+                code: Some(code.clone()),
+                name: client.name.clone(),
+                password: None,
+            }));
+            sync(&clients[..=i]);
+        }
+
+        clients
+    }
+
+    // four players, the first two are the red and blue spymasters
+    pub fn game(&self) -> Vec<Client> {
+        let clients = self.room(&["alice", "bob", "carol", "dave"]);
+
+        for (i, client) in clients.iter().enumerate() {
+            client.team(if i % 2 == 0 { Team::Red } else { Team::Blue });
+            sync(&clients);
+        }
+
+        clients[0].start("alice", "bob");
+        let view = sync(&clients);
+        assert_eq!(view.state, "play");
+
+        // spymasters receive the key after the room update
+        clients[0].tiles();
+        clients[1].tiles();
+
+        clients
+    }
+
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(server) = self.server.take() {
+            self.handle.shutdown();
+            match server.join() {
+                Ok(result) => result.expect("server failed"),
+                Err(_) if thread::panicking() => {},
+                Err(_) => panic!("server panicked")
+            }
+        }
+    }
+
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl Client {
+
+    pub fn send(&self, request: Request) {
+        self.connection.send(request);
+    }
+
+    pub fn send_text(&self, text: &str) {
+        self.connection.send_text(text);
+    }
+
+    pub fn token(&self) -> usize {
+        self.connection.token.0
+    }
+
+    pub fn disconnect(&self) {
+        self.connection.close();
+    }
+
+    // answered once the server handled everything this client's earlier events raced with
+    pub fn health(&self) -> u16 {
+        self.http("/health").0
+    }
+
+    pub fn metrics(&self) -> String {
+        self.http("/metrics").1
+    }
+
+    fn http(&self, path: &str) -> (u16, String) {
+        self.connection.http(path);
+        match self.recv() {
+            Output::Http(status, body) => (status, body),
+            output => panic!("{} expected an http response, received {:?}", self.name, output)
+        }
+    }
+
+    pub fn chat(&self, message: &str) {
+        self.send(Request::Chat(request::Chat {
+            channel: Channel::All,
+            message: message.to_string(),
+        }));
+    }
+
+    pub fn create(&self) -> View {
+        self.send(Request::Room(request::Room {
+            name: self.name.clone(),
+            language: "en".to_string(),
+            password: None,
+        }));
+        self.room()
+    }
+
+    pub fn team(&self, team: Team) {
+        self.send(Request::Team(request::Team { team }));
+    }
+
+    pub fn start(&self, red: &str, blue: &str) {
+        self.start_bots(red, blue, Vec::new());
+    }
+
+    pub fn start_bots(&self, red: &str, blue: &str, bots: Vec<Team>) {
+        self.send(Request::Start(request::Start {
+            red: red.to_string(),
+            blue: blue.to_string(),
+            bots,
+        }));
+    }
+
+    pub fn hint(&self, hint: &str, guesses: u8) {
+        self.send(Request::Hint(request::Hint {
+            hint: hint.to_string(),
+            guesses,
+        }));
+    }
+
+    pub fn guess(&self, x: usize, y: usize) {
+        self.send(Request::Guess(request::Guess { x, y }));
+    }
+
+    pub fn pass(&self) {
+        self.send(Request::Pass(request::Pass {}));
+    }
+
+    pub fn reset(&self) {
+        self.send(Request::Reset(request::Reset {
+            language: "en".to_string(),
+        }));
+    }
+
+    pub fn recv(&self) -> Output {
+        match self.connection.recv(TIMEOUT) {
+            Ok(output) => output,
+            Err(RecvTimeoutError::Timeout) => panic!("{} received nothing", self.name),
+            Err(RecvTimeoutError::Disconnected) => panic!("{} was disconnected", self.name),
+        }
+    }
+
+    pub fn response(&self) -> Response {
+        match self.recv() {
+            Output::Response(response) => response,
+            output => panic!("{} expected a response, received {:?}", self.name, output)
+        }
+    }
+
+    // the next room update, chat and tiles are skipped
+    pub fn room(&self) -> View {
+        loop {
+            match self.response() {
+                Response::Room { room } => return *room,
+                Response::Error { error } => panic!("{} expected a room, received error: {}", self.name, error),
+                Response::Tiles { .. } | Response::Chat { .. } => {},
+                response => panic!("{} expected a room, received {:?}", self.name, response)
+            }
+        }
+    }
+
+    pub fn tiles(&self) -> TileMap {
+        match self.response() {
+            Response::Tiles { tiles } => tiles,
+            response => panic!("{} expected tiles, received {:?}", self.name, response)
+        }
+    }
+
+    pub fn error(&self) -> String {
+        match self.response() {
+            Response::Error { error } => error,
+            response => panic!("{} expected an error, received {:?}", self.name, response)
+        }
+    }
+
+    pub fn assert_quiet(&self) {
+        if let Ok(output) = self.connection.recv(QUIET) {
+            panic!("{} expected nothing, received {:?}", self.name, output)
+        }
+    }
+
+    // true once the server dropped the connection
+    pub fn closed(&self) -> bool {
+        loop {
+            match self.connection.recv(TIMEOUT) {
+                Ok(Output::Close(_, _)) | Err(RecvTimeoutError::Disconnected) => return true,
+                Ok(_) => {},
+                Err(RecvTimeoutError::Timeout) => return false,
+            }
+        }
+    }
+
+}
+
+// waits for the room update to reach every client and returns it
+pub fn sync(clients: &[Client]) -> View {
+    let views: Vec<View> = clients.iter().map(|client| client.room()).collect();
+    for view in views.iter() {
+        assert_eq!(view.id, views[0].id);
+        assert_eq!(view.players.len(), views[0].players.len());
+    }
+    views.into_iter().next().expect("no clients")
+}
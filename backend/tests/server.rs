@@ -0,0 +1,538 @@
+mod harness;
+
+use codename::ratelimit;
+use codename::request::{self, Request};
+use codename::server;
+use codename::room::View;
+use codename_engine::board::Tile;
+use codename_engine::game::State;
+use codename_engine::team::Team;
+use harness::{clue, sync, Harness};
+use std::collections::HashMap;
+
+#[test]
+fn room_creation() {
+    let harness = Harness::start();
+    let alice = harness.connect("alice");
+
+    let view = alice.create();
+    assert_eq!(view.state, "join");
+    assert_eq!(view.players, vec!["alice"]);
+    assert_eq!(view.code.len(), 4);
+    assert!(!view.private);
+
+    harness.stop();
+}
+
+#[test]
+fn room_creation_limit_per_address() {
+    let harness = Harness::with_config(server::Config {
+        workers: 1,
+        max_rooms_per_ip: Some(1),
+        rate_limit: ratelimit::Config {
+            enabled: false,
+            ..ratelimit::Config::default()
+        },
+        ..server::Config::default()
+    });
+    let alice = harness.connect("alice");
+    let bob = harness.connect("bob");
+
+    alice.create();
+    alice.disconnect();
+
+    // in memory connections all come from the same address
+    bob.send(Request::Room(request::Room {
+        name: "bob".to_string(),
+        language: "en".to_string(),
+        password: None,
+    }));
+    assert_eq!(bob.error(), "too many rooms created from this address");
+}
+
+#[test]
+fn room_creation_unknown_language() {
+    let harness = Harness::start();
+    let alice = harness.connect("alice");
+
+    alice.send(Request::Room(request::Room {
+        name: "alice".to_string(),
+        language: "xx".to_string(),
+        password: None,
+    }));
+    assert!(alice.error().contains("xx"));
+}
+
+#[test]
+fn parse_errors_are_rate_limited() {
+    let mut kinds = HashMap::new();
+    kinds.insert("parse".to_string(), ratelimit::Limit { rate: 0.0, burst: 1.0 });
+    let harness = Harness::with_config(server::Config {
+        workers: 1,
+        rate_limit: ratelimit::Config {
+            enabled: true,
+            default: ratelimit::Limit { rate: 0.0, burst: 1.0 },
+            kinds,
+            max_violations: 1.0,
+        },
+        ..server::Config::default()
+    });
+    let alice = harness.connect("alice");
+
+    alice.send_text("not json");
+    assert!(alice.error().starts_with("parse error"));
+    alice.send_text("not json");
+    assert_eq!(alice.error(), "rate limit exceeded for parse requests");
+    alice.send_text("not json");
+    assert!(alice.closed());
+}
+
+#[test]
+fn request_before_joining() {
+    let harness = Harness::start();
+    let alice = harness.connect("alice");
+
+    alice.team(Team::Red);
+    assert!(alice.error().starts_with("forbidden request"));
+}
+
+#[test]
+fn parse_error() {
+    let harness = Harness::start();
+    let alice = harness.connect("alice");
+
+    alice.send_text("{\"request\": \"dance\"}");
+    assert_eq!(alice.error(), "unknown request: dance");
+
+    alice.send_text("not json");
+    assert!(alice.error().starts_with("parse error"));
+}
+
+#[test]
+fn join_by_code_and_id() {
+    let harness = Harness::start();
+    let alice = harness.connect("alice");
+    let bob = harness.connect("bob");
+    let carol = harness.connect("carol");
+
+    let view = alice.create();
+
+    bob.send(Request::Join(request::Join {
+        id: None,
+        code: Some(view.code.to_lowercase()),
+        name: "bob".to_string(),
+        password: None,
+    }));
+    let joined = bob.room();
+    assert_eq!(joined.id, view.id);
+    assert_eq!(alice.room().players.len(), 2);
+
+    carol.send(Request::Join(request::Join {
+        id: Some(view.id),
+        code: None,
+        name: "carol".to_string(),
+        password: None,
+    }));
+    let joined = carol.room();
+    assert_eq!(joined.id, view.id);
+    assert_eq!(joined.players.len(), 3);
+    assert_eq!(alice.room().players.len(), 3);
+    assert_eq!(bob.room().players.len(), 3);
+}
+
+#[test]
+fn private_room() {
+    let harness = Harness::start();
+    let alice = harness.connect("alice");
+    let bob = harness.connect("bob");
+
+    alice.send(Request::Room(request::Room {
+        name: "alice".to_string(),
+        language: "en".to_string(),
+        password: Some("secret".to_string()),
+    }));
+    let view = alice.room();
+    assert!(view.private);
+
+    let join = |password: Option<&str>| Request::Join(request::Join {
+        id: Some(view.id),
+        code: None,
+        name: "bob".to_string(),
+        password: password.map(str::to_string),
+    });
+
+    bob.send(join(None));
+    assert_eq!(bob.error(), "invalid room password");
+    bob.send(join(Some("Secret")));
+    assert_eq!(bob.error(), "invalid room password");
+
+    bob.send(join(Some("secret")));
+    assert_eq!(bob.room().players.len(), 2);
+    assert_eq!(alice.room().players.len(), 2);
+}
+
+#[test]
+fn join_unknown_room() {
+    let harness = Harness::start();
+    let bob = harness.connect("bob");
+
+    bob.send(Request::Join(request::Join {
+        id: None,
+        code: Some("ZZZZ".to_string()),
+        name: "bob".to_string(),
+        password: None,
+    }));
+    assert!(bob.error().starts_with("forbidden request"));
+}
+
+#[test]
+fn team_selection() {
+    let harness = Harness::start();
+    let clients = harness.room(&["alice", "bob", "carol", "dave"]);
+
+    clients[0].team(Team::Red);
+    let view = sync(&clients);
+    assert_eq!(view.state, "team");
+    assert_eq!(view.game.red.players.len(), 1);
+    assert!(view.game.blue.players.is_empty());
+
+    clients[0].team(Team::Blue);
+    let view = sync(&clients);
+    assert!(view.game.blue.players.values().any(|name| name == "alice"));
+}
+
+#[test]
+fn start_requires_players() {
+    let harness = Harness::start();
+    let clients = harness.room(&["alice", "bob", "carol", "dave"]);
+
+    clients[0].team(Team::Red);
+    sync(&clients);
+    clients[2].team(Team::Red);
+    sync(&clients);
+
+    clients[0].start("alice", "bob");
+    assert_eq!(clients[0].error(), "game error: team blue does not have enough players");
+}
+
+#[test]
+fn start() {
+    let harness = Harness::start();
+    let clients = harness.room(&["alice", "bob", "carol", "dave"]);
+
+    for (i, client) in clients.iter().enumerate() {
+        client.team(if i % 2 == 0 { Team::Red } else { Team::Blue });
+        sync(&clients);
+    }
+
+    clients[1].start("alice", "bob");
+    assert_eq!(clients[1].error(), "game error: player is not the admin");
+
+    clients[0].start("alice", "bob");
+    let view = sync(&clients);
+    assert_eq!(view.state, "play");
+    assert!(matches!(view.game.state, State::Play(Team::Red)));
+
+    // only the spymasters see the key
+    assert_eq!(clients[0].tiles()[4][4], Tile::Death);
+    assert_eq!(clients[1].tiles()[0][0], Tile::Red);
+    clients[2].assert_quiet();
+    clients[3].assert_quiet();
+
+    clients[0].start("alice", "bob");
+    assert_eq!(clients[0].error(), "game error: game has already started");
+}
+
+#[test]
+fn hint_and_guess() {
+    let harness = Harness::start();
+    let clients = harness.game();
+
+    clients[2].hint("fruit", 2);
+    assert_eq!(clients[2].error(), "game error: player is not a master");
+
+    clients[0].hint("fruit", 2);
+    let view = sync(&clients);
+    assert_eq!(view.game.red.hint, "fruit");
+    assert_eq!(view.game.red.guesses, 2);
+
+    clients[3].guess(0, 0);
+    assert_eq!(clients[3].error(), "game error: not your turn to give a guess");
+
+    // a red tile keeps the turn, a blue one hands it over
+    clients[2].guess(0, 0);
+    let view = sync(&clients);
+    assert!(view.game.board.cards[0][0]);
+    assert!(matches!(view.game.state, State::Play(Team::Red)));
+
+    clients[2].guess(2, 0);
+    let view = sync(&clients);
+    assert!(view.game.board.cards[2][0]);
+    assert!(matches!(view.game.state, State::Play(Team::Blue)));
+
+    clients[2].guess(0, 1);
+    assert_eq!(clients[2].error(), "game error: not your turn to give a guess");
+}
+
+#[test]
+fn guess_revealed_card() {
+    let harness = Harness::start();
+    let clients = harness.game();
+
+    clients[0].hint("fruit", 3);
+    sync(&clients);
+    clients[2].guess(0, 0);
+    sync(&clients);
+
+    clients[2].guess(0, 0);
+    assert!(clients[2].error().starts_with("game error"));
+}
+
+#[test]
+fn pass() {
+    let harness = Harness::start();
+    let clients = harness.game();
+
+    clients[3].pass();
+    assert_eq!(clients[3].error(), "game error: player not found");
+
+    clients[0].hint("fruit", 1);
+    sync(&clients);
+
+    clients[2].pass();
+    let view = sync(&clients);
+    assert!(matches!(view.game.state, State::Play(Team::Blue)));
+    assert_eq!(view.state, "play");
+}
+
+#[test]
+fn assassin_ends_the_game() {
+    let harness = Harness::start();
+    let clients = harness.game();
+
+    clients[0].hint("fruit", 1);
+    sync(&clients);
+
+    clients[2].guess(4, 4);
+    let view = sync(&clients);
+    assert_eq!(view.state, "end");
+    assert!(matches!(view.game.state, State::End(Team::Blue)));
+
+    clients[0].hint("again", 1);
+    assert!(clients[0].error().starts_with("game error"));
+}
+
+#[test]
+fn reset() {
+    let harness = Harness::start();
+    let clients = harness.game();
+
+    clients[1].reset();
+    assert_eq!(clients[1].error(), "game error: player is not the admin");
+
+    clients[0].reset();
+    let view = sync(&clients);
+    assert_eq!(view.state, "team");
+    assert!(matches!(view.game.state, State::Start));
+    assert!(view.game.red.players.is_empty());
+    assert_eq!(view.players.len(), 4);
+}
+
+#[test]
+fn games_are_counted() {
+    let harness = Harness::start();
+    let clients = harness.game();
+    assert!(clients[0].metrics().contains("codename_games_started_total 1\n"));
+
+    clients[0].hint("fruit", 1);
+    sync(&clients);
+    clients[2].guess(4, 4);
+    assert_eq!(sync(&clients).state, "end");
+
+    clients[0].reset();
+    sync(&clients);
+    for (i, client) in clients.iter().enumerate() {
+        client.team(if i % 2 == 0 { Team::Red } else { Team::Blue });
+        sync(&clients);
+    }
+    clients[0].start("alice", "bob");
+    assert_eq!(sync(&clients).state, "play");
+    clients[0].tiles();
+    clients[1].tiles();
+
+    let metrics = clients[0].metrics();
+    assert!(metrics.contains("codename_games_started_total 2\n"), "{}", metrics);
+    assert!(metrics.contains("codename_games_finished_total 1\n"), "{}", metrics);
+}
+
+#[test]
+fn disconnect_player() {
+    let harness = Harness::start();
+    let clients = harness.game();
+
+    clients[3].disconnect();
+    let remaining = &clients[..3];
+    let view = sync(remaining);
+    assert_eq!(view.players.len(), 3);
+    assert_eq!(view.state, "join");
+
+    // the blue team lost its only guesser, the game waits for players
+    clients[0].hint("fruit", 1);
+    assert_eq!(clients[0].error(), "game error: not your turn to give a hint");
+}
+
+#[test]
+fn disconnect_admin_closes_room() {
+    let harness = Harness::start();
+    let erin = harness.connect("erin");
+    let code = erin.create().code;
+    let clients = harness.game();
+
+    clients[0].disconnect();
+    for client in clients.iter().skip(1) {
+        assert!(client.closed(), "{} is still connected", client.name);
+    }
+
+    // other rooms are unaffected
+    let frank = harness.connect("frank");
+    frank.send(Request::Join(request::Join {
+        id: None,
+        code: Some(code),
+        name: "frank".to_string(),
+        password: None,
+    }));
+    assert_eq!(frank.room().players.len(), 2);
+}
+
+#[test]
+fn spymaster_bot_requires_embeddings() {
+    let harness = Harness::start();
+    let clients = harness.room(&["alice", "bob", "carol"]);
+
+    for (i, client) in clients.iter().enumerate() {
+        client.team(if i == 0 { Team::Red } else { Team::Blue });
+        sync(&clients);
+    }
+
+    clients[0].start_bots("", "bob", vec![Team::Red]);
+    assert_eq!(clients[0].error(), "spymaster bot unavailable: no word embeddings loaded");
+}
+
+// the card of the word the last red hint points to
+fn hinted(view: &View) -> (usize, usize) {
+    (0..25)
+        .map(|i| (i / 5, i % 5))
+        .find(|(x, y)| clue(&view.game.board.words[*x][*y]) == view.game.red.hint)
+        .expect("hint does not match a word")
+}
+
+#[test]
+fn spymaster_bot() {
+    let harness = Harness::with_spymaster();
+    let clients = harness.room(&["alice", "bob", "carol"]);
+
+    for (i, client) in clients.iter().enumerate() {
+        client.team(if i == 0 { Team::Red } else { Team::Blue });
+        sync(&clients);
+    }
+
+    // the bot takes the red spymaster seat and gives its hint as soon as the game starts
+    clients[0].start_bots("", "bob", vec![Team::Red]);
+    let view = sync(&clients);
+    assert_eq!(view.state, "play");
+    assert!(view.game.red.players.values().any(|name| name == "spymaster bot"));
+    assert_eq!(clients[1].tiles()[0][0], Tile::Red);
+
+    let view = sync(&clients);
+    let (x, y) = hinted(&view);
+    assert_eq!(view.game.board.tiles[x][y], Tile::Red);
+    assert_eq!(view.game.red.guesses, 1);
+
+    // a single guess allowed, the turn goes to blue
+    clients[0].guess(x, y);
+    let view = sync(&clients);
+    assert!(matches!(view.game.state, State::Play(Team::Blue)));
+
+    clients[1].hint("ocean", 1);
+    sync(&clients);
+    clients[2].pass();
+
+    // back to red, the bot hints again for the words left
+    sync(&clients);
+    let view = sync(&clients);
+    assert!(matches!(view.game.state, State::Play(Team::Red)));
+    let (next_x, next_y) = hinted(&view);
+    assert_eq!(view.game.board.tiles[next_x][next_y], Tile::Red);
+    assert!(!view.game.board.cards[next_x][next_y]);
+}
+
+#[test]
+fn tokens_are_reused() {
+    let harness = Harness::start();
+    let alice = harness.connect("alice");
+    let probe = harness.connect("probe");
+
+    // alice never joined a room, nothing can be in flight for her token
+    alice.disconnect();
+    assert_eq!(probe.health(), 200);
+
+    let bob = harness.connect("bob");
+    assert_eq!(bob.token(), alice.token());
+    assert_eq!(bob.create().players, vec!["bob"]);
+}
+
+#[test]
+fn reconnect_after_disconnect() {
+    let harness = Harness::start();
+    let clients = harness.game();
+    let probe = harness.connect("probe");
+
+    // room traffic for dave is still queued on the worker when he leaves
+    for _ in 0..200 {
+        clients[0].chat("hello");
+    }
+    clients[3].disconnect();
+    assert_eq!(probe.health(), 200);
+
+    // the next connection may get dave's token back, but none of his messages
+    let erin = harness.connect("erin");
+    erin.assert_quiet();
+    let view = erin.create();
+    assert_eq!(view.players, vec!["erin"]);
+    erin.assert_quiet();
+}
+
+#[test]
+fn shutdown_notifies_players() {
+    let harness = Harness::start();
+    let clients = harness.game();
+
+    harness.stop();
+    for client in clients.iter() {
+        assert!(client.closed());
+    }
+}
+
+#[test]
+fn shutdown_writes_snapshot() {
+    let path = std::env::temp_dir().join(format!("codename-snapshot-{}.json", std::process::id()));
+    let harness = Harness::with_config(server::Config {
+        workers: 2,
+        snapshot: Some(path.clone()),
+        ..server::Config::default()
+    });
+    let clients = harness.game();
+    clients[0].hint("fruit", 1);
+    sync(&clients);
+
+    harness.stop();
+    let snapshot: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let rooms = snapshot.as_array().unwrap();
+    assert_eq!(rooms.len(), 1);
+    assert_eq!(rooms[0]["room"]["state"], "play");
+    assert_eq!(rooms[0]["room"]["players"].as_array().unwrap().len(), 4);
+    assert_eq!(rooms[0]["room"]["game"]["red"]["hint"], "fruit");
+    assert_eq!(rooms[0]["tiles"][4][4], "death");
+}
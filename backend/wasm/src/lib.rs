@@ -0,0 +1,123 @@
+use wasm_bindgen::prelude::*;
+use codename_engine::action::{Start, Hint, Guess, Validate};
+use codename_engine::board::BoardSet;
+use codename_engine::game::{Game, State};
+use codename_engine::player::PlayerId;
+use codename_engine::team::Team;
+use std::fmt::Display;
+
+// pass-and-play: both teams share one device, so each team is played by a
+// spymaster and a single guesser acting for everyone else
+const RED_MASTER: PlayerId = PlayerId(0);
+const RED_GUESSER: PlayerId = PlayerId(1);
+const BLUE_MASTER: PlayerId = PlayerId(2);
+const BLUE_GUESSER: PlayerId = PlayerId(3);
+
+#[wasm_bindgen]
+pub struct LocalGame {
+    game: Game,
+}
+
+#[wasm_bindgen]
+impl LocalGame {
+
+    // boards is the JSON board set file served to the frontend
+    #[wasm_bindgen(constructor)]
+    pub fn new(boards: &str, language: &str) -> Result<LocalGame, JsValue> {
+        let boards: BoardSet = serde_json::from_str(boards).map_err(error)?;
+        let board = boards.new_board(language).map_err(error)?;
+
+        let mut game = Game::new(board, RED_MASTER);
+        game.add_player(RED_MASTER, Team::Red, "red spymaster");
+        game.add_player(RED_GUESSER, Team::Red, "red");
+        game.add_player(BLUE_MASTER, Team::Blue, "blue spymaster");
+        game.add_player(BLUE_GUESSER, Team::Blue, "blue");
+
+        let start = Start {
+            red: "red spymaster".to_string(),
+            blue: "blue spymaster".to_string(),
+            bots: Vec::new(),
+        };
+        game.start(RED_MASTER, &start).map_err(error)?;
+
+        Ok(LocalGame {
+            game,
+        })
+    }
+
+    pub fn hint(&mut self, hint: &str, guesses: u8) -> Result<(), JsValue> {
+        let hint = Hint {
+            hint: hint.to_string(),
+            guesses,
+        };
+        hint.validate().map_err(error)?;
+
+        let (master, _) = self.players()?;
+        self.game.hint(master, &hint).map_err(error)
+    }
+
+    pub fn guess(&mut self, x: usize, y: usize) -> Result<(), JsValue> {
+        let guess = Guess {
+            x,
+            y,
+        };
+        guess.validate().map_err(error)?;
+
+        let (_, guesser) = self.players()?;
+        self.game.guess(guesser, &guess).map_err(error)
+    }
+
+    pub fn pass(&mut self) -> Result<(), JsValue> {
+        let (_, guesser) = self.players()?;
+        self.game.pass(guesser).map_err(error)
+    }
+
+    // the team whose turn it is, or the winner once the game is over
+    pub fn turn(&self) -> String {
+        match self.game.state {
+            State::Start => name(self.game.board.start_team()),
+            State::Play(team) | State::End(team) => name(team),
+        }.to_string()
+    }
+
+    pub fn winner(&self) -> Option<String> {
+        match self.game.state {
+            State::End(team) => Some(name(team).to_string()),
+            _ => None
+        }
+    }
+
+    // game state as sent to players by the server
+    pub fn state(&self) -> Result<String, JsValue> {
+        serde_json::to_string(&self.game).map_err(error)
+    }
+
+    // the colour of every tile, for the spymaster view
+    pub fn tiles(&self) -> Result<String, JsValue> {
+        serde_json::to_string(&self.game.board.tiles).map_err(error)
+    }
+
+}
+
+impl LocalGame {
+
+    fn players(&self) -> Result<(PlayerId, PlayerId), JsValue> {
+        match self.game.state {
+            State::Play(Team::Red) => Ok((RED_MASTER, RED_GUESSER)),
+            State::Play(Team::Blue) => Ok((BLUE_MASTER, BLUE_GUESSER)),
+            _ => Err(JsValue::from_str("game is over"))
+        }
+    }
+
+}
+
+fn name(team: Team) -> &'static str {
+    match team {
+        Team::Red => "red",
+        Team::Blue => "blue",
+    }
+}
+
+fn error<E: Display>(e: E) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
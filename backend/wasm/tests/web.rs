@@ -0,0 +1,95 @@
+#![cfg(target_arch = "wasm32")]
+
+use codename_wasm::LocalGame;
+use wasm_bindgen_test::*;
+
+const BOARDS: &str = r#"{
+    "words": {
+        "en": [
+            "w0", "w1", "w2", "w3", "w4", "w5", "w6", "w7", "w8", "w9",
+            "w10", "w11", "w12", "w13", "w14", "w15", "w16", "w17", "w18", "w19",
+            "w20", "w21", "w22", "w23", "w24"
+        ]
+    },
+    "tiles": [
+        [
+            ["red", "red", "red", "red", "red"],
+            ["red", "red", "red", "red", "blue"],
+            ["blue", "blue", "blue", "blue", "blue"],
+            ["blue", "blue", "neutral", "neutral", "neutral"],
+            ["neutral", "neutral", "neutral", "neutral", "death"]
+        ]
+    ]
+}"#;
+
+fn new_game() -> LocalGame {
+    LocalGame::new(BOARDS, "en").unwrap()
+}
+
+#[wasm_bindgen_test]
+fn unknown_language() {
+    assert!(LocalGame::new(BOARDS, "fr").is_err());
+}
+
+#[wasm_bindgen_test]
+fn red_starts_with_more_tiles() {
+    let game = new_game();
+    assert_eq!(game.turn(), "red");
+    assert_eq!(game.winner(), None);
+}
+
+#[wasm_bindgen_test]
+fn guess_before_hint() {
+    let mut game = new_game();
+    assert!(game.guess(0, 0).is_err());
+}
+
+#[wasm_bindgen_test]
+fn invalid_hint() {
+    let mut game = new_game();
+    assert!(game.hint("", 1).is_err());
+    assert!(game.hint("animal", 0).is_err());
+}
+
+#[wasm_bindgen_test]
+fn wrong_tile_ends_turn() {
+    let mut game = new_game();
+    game.hint("animal", 2).unwrap();
+    game.guess(0, 0).unwrap();
+    assert_eq!(game.turn(), "red");
+
+    game.guess(3, 2).unwrap();
+    assert_eq!(game.turn(), "blue");
+}
+
+#[wasm_bindgen_test]
+fn pass_ends_turn() {
+    let mut game = new_game();
+    game.hint("animal", 2).unwrap();
+    game.guess(0, 0).unwrap();
+    game.pass().unwrap();
+    assert_eq!(game.turn(), "blue");
+}
+
+#[wasm_bindgen_test]
+fn death_tile_loses() {
+    let mut game = new_game();
+    game.hint("animal", 1).unwrap();
+    game.guess(4, 4).unwrap();
+    assert_eq!(game.winner(), Some("blue".to_string()));
+    assert!(game.pass().is_err());
+}
+
+#[wasm_bindgen_test]
+fn state_hides_unturned_tiles() {
+    let mut game = new_game();
+    game.hint("animal", 1).unwrap();
+    game.guess(0, 0).unwrap();
+
+    let state: serde_json::Value = serde_json::from_str(&game.state().unwrap()).unwrap();
+    assert_eq!(state["board"]["cards"][0][0], "red");
+    assert!(state["board"]["cards"][0][1].is_null());
+
+    let tiles: serde_json::Value = serde_json::from_str(&game.tiles().unwrap()).unwrap();
+    assert_eq!(tiles[4][4], "death");
+}